@@ -0,0 +1,193 @@
+use crate::size::{varint_size, MaxSize};
+use core::{
+    fmt,
+    ops::{Deref, DerefMut},
+};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Error returned when data does not fit in a fixed-capacity buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CapacityError;
+
+impl fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("data exceeds buffer capacity")
+    }
+}
+
+/// Byte buffer with a capacity of `N`, serialized as a byte string.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Bytes<const N: usize>(pub heapless::Vec<u8, N>);
+
+impl<const N: usize> Bytes<N> {
+    /// Create an empty buffer.
+    pub const fn new() -> Self {
+        Self(heapless::Vec::new())
+    }
+}
+
+impl<const N: usize> Deref for Bytes<N> {
+    type Target = heapless::Vec<u8, N>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<const N: usize> DerefMut for Bytes<N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<const N: usize> AsRef<[u8]> for Bytes<N> {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<const N: usize> TryFrom<&[u8]> for Bytes<N> {
+    type Error = CapacityError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        heapless::Vec::from_slice(data)
+            .map(Self)
+            .map_err(|_| CapacityError)
+    }
+}
+
+impl<const N: usize> MaxSize for Bytes<N> {
+    const MAX_SIZE: usize = varint_size(N) + N;
+}
+
+impl<const N: usize> Serialize for Bytes<N> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for Bytes<N> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct BytesVisitor<const N: usize>;
+
+        impl<'de, const N: usize> de::Visitor<'de> for BytesVisitor<N> {
+            type Value = Bytes<N>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "at most {} bytes", N)
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                Bytes::try_from(v).map_err(|_| E::invalid_length(v.len(), &self))
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut bytes = Bytes::new();
+                while let Some(byte) = seq.next_element()? {
+                    bytes
+                        .0
+                        .push(byte)
+                        .map_err(|_| de::Error::invalid_length(N + 1, &self))?;
+                }
+                Ok(bytes)
+            }
+        }
+
+        deserializer.deserialize_bytes(BytesVisitor::<N>)
+    }
+}
+
+/// String with a capacity of `N` bytes, serialized as a string.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct FixedString<const N: usize>(pub heapless::String<N>);
+
+impl<const N: usize> FixedString<N> {
+    /// Create an empty string.
+    pub const fn new() -> Self {
+        Self(heapless::String::new())
+    }
+}
+
+impl<const N: usize> Deref for FixedString<N> {
+    type Target = heapless::String<N>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<const N: usize> DerefMut for FixedString<N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<const N: usize> AsRef<str> for FixedString<N> {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<const N: usize> TryFrom<&str> for FixedString<N> {
+    type Error = CapacityError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let mut string = heapless::String::new();
+        string.push_str(s).map_err(|_| CapacityError)?;
+        Ok(Self(string))
+    }
+}
+
+impl<const N: usize> From<heapless::String<N>> for FixedString<N> {
+    fn from(s: heapless::String<N>) -> Self {
+        Self(s)
+    }
+}
+
+impl<const N: usize> fmt::Display for FixedString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl<const N: usize> MaxSize for FixedString<N> {
+    const MAX_SIZE: usize = varint_size(N) + N;
+}
+
+impl<const N: usize> Serialize for FixedString<N> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for FixedString<N> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct StrVisitor<const N: usize>;
+
+        impl<const N: usize> de::Visitor<'_> for StrVisitor<N> {
+            type Value = FixedString<N>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a string of at most {} bytes", N)
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                FixedString::try_from(v).map_err(|_| E::invalid_length(v.len(), &self))
+            }
+        }
+
+        deserializer.deserialize_str(StrVisitor::<N>)
+    }
+}
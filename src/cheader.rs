@@ -0,0 +1,176 @@
+use crate::{
+    control::{FaultSpec, InvariantKind},
+    envelope::TaskId,
+    fixed::{FixedField, RECORD_SIZE},
+    fs::{FileMode, OpenFlags, Path, UnlinkatFlags, MAX_PATH_LEN},
+    mem::{MmapFlags, ProtFlags},
+    CommandDesc, COMMANDS, ID_SIZE,
+};
+use alloc::string::String;
+use bitflags::Flags;
+use core::fmt::Write;
+
+/// Generate a C header with command ids, flag constants and the layout of
+/// the fixed-size record of every command.
+///
+/// Records are those of the [`fixed`](crate::fixed) encoding: each struct
+/// starts with the command id and lists the fields at their encoded width,
+/// packed and little-endian, so a C harness on a little-endian target can
+/// cast a record buffer to the struct of its id.
+///
+/// # Panics
+///
+/// Panics if a command has a field type without a fixed-width C layout.
+pub fn generate() -> String {
+    let mut out = String::new();
+    out.push_str("/* Generated by km-command. Do not edit. */\n\n");
+    out.push_str("#ifndef KM_COMMAND_H\n#define KM_COMMAND_H\n\n");
+    out.push_str("#include <stdint.h>\n\n");
+    writeln!(out, "#define KM_MAX_PATH_LEN {}", MAX_PATH_LEN).unwrap();
+    writeln!(out, "#define KM_ID_SIZE {}", ID_SIZE).unwrap();
+    writeln!(out, "#define KM_RECORD_SIZE {}\n", RECORD_SIZE).unwrap();
+
+    out.push_str("/* Command ids */\n");
+    for desc in COMMANDS {
+        writeln!(
+            out,
+            "#define KM_CMD_{} {}",
+            desc.name.to_uppercase(),
+            desc.id
+        )
+        .unwrap();
+    }
+    out.push('\n');
+
+    write_flags::<OpenFlags>(&mut out, "OpenFlags");
+    write_flags::<FileMode>(&mut out, "FileMode");
+    write_flags::<UnlinkatFlags>(&mut out, "UnlinkatFlags");
+    write_flags::<ProtFlags>(&mut out, "ProtFlags");
+    write_flags::<MmapFlags>(&mut out, "MmapFlags");
+    out.push_str(ENUM_TYPES);
+
+    for desc in COMMANDS {
+        write_struct(&mut out, desc);
+    }
+
+    out.push_str("#endif /* KM_COMMAND_H */\n");
+    out
+}
+
+/// Layouts of the enum field types, matching their `FixedField` encoding.
+const ENUM_TYPES: &str = "\
+/* InvariantKind */
+#define KM_INVARIANT_ALL 0
+#define KM_INVARIANT_PAGE_TABLES 1
+#define KM_INVARIANT_FD_REFCOUNTS 2
+#define KM_INVARIANT_ALLOCATOR 3
+#define KM_INVARIANT_CUSTOM 4
+
+struct __attribute__((packed)) km_invariant_kind {
+    uint32_t kind;
+    uint32_t custom;
+};
+
+/* FaultSpec */
+#define KM_FAULT_FAIL_ALLOC 0
+#define KM_FAULT_FAIL_SYSCALL 1
+#define KM_FAULT_FAIL_READ 2
+#define KM_FAULT_FAIL_WRITE 3
+#define KM_FAULT_CLEAR 4
+
+struct __attribute__((packed)) km_fault_spec {
+    uint32_t kind;
+    uint32_t count;
+    uint64_t id;
+    int64_t errno_;
+};
+
+";
+
+fn write_flags<F>(out: &mut String, name: &str)
+where
+    F: Flags,
+    F::Bits: Into<u64>,
+{
+    writeln!(out, "/* {} */", name).unwrap();
+    let prefix = name.to_uppercase();
+    for flag in F::FLAGS {
+        writeln!(
+            out,
+            "#define KM_{}_{} {:#x}",
+            prefix,
+            flag.name(),
+            flag.value().bits().into()
+        )
+        .unwrap();
+    }
+    out.push('\n');
+}
+
+fn write_struct(out: &mut String, desc: &CommandDesc) {
+    writeln!(out, "struct __attribute__((packed)) km_{} {{", desc.name).unwrap();
+    out.push_str("    uint32_t id;\n");
+    for field in desc.fields {
+        let (ty, _) =
+            c_type(field.ty).unwrap_or_else(|| panic!("unsupported field type `{}`", field.ty));
+        match ty.strip_suffix("[]") {
+            Some(elem) => writeln!(out, "    {} {}[KM_MAX_PATH_LEN];", elem, field.name).unwrap(),
+            None => writeln!(out, "    {} {};", ty, field.name).unwrap(),
+        }
+    }
+    out.push_str("};\n\n");
+}
+
+/// Map a Rust field type to the C type of its fixed-width encoding and its
+/// size. Paths map to `char[]`, an array of `KM_MAX_PATH_LEN` bytes.
+pub(crate) fn c_type(ty: &str) -> Option<(&'static str, usize)> {
+    let c_type = match ty {
+        "bool" => ("uint8_t", bool::SIZE),
+        "u8" => ("uint8_t", u8::SIZE),
+        "i8" => ("int8_t", i8::SIZE),
+        "u16" => ("uint16_t", u16::SIZE),
+        "i16" => ("int16_t", i16::SIZE),
+        "u32" => ("uint32_t", u32::SIZE),
+        "i32" => ("int32_t", i32::SIZE),
+        "u64" => ("uint64_t", u64::SIZE),
+        "i64" => ("int64_t", i64::SIZE),
+        "usize" => ("uint64_t", usize::SIZE),
+        "isize" => ("int64_t", isize::SIZE),
+        "OpenFlags" => flags_type::<OpenFlags>(),
+        "FileMode" => flags_type::<FileMode>(),
+        "UnlinkatFlags" => flags_type::<UnlinkatFlags>(),
+        "ProtFlags" => flags_type::<ProtFlags>(),
+        "MmapFlags" => flags_type::<MmapFlags>(),
+        "TaskId" => ("uint32_t", TaskId::SIZE),
+        "Path" => ("char[]", Path::SIZE),
+        "InvariantKind" => ("struct km_invariant_kind", InvariantKind::SIZE),
+        "FaultSpec" => ("struct km_fault_spec", FaultSpec::SIZE),
+        _ => return None,
+    };
+    Some(c_type)
+}
+
+/// C type of the bits of a flags type.
+fn flags_type<F: Flags + FixedField>() -> (&'static str, usize) {
+    let ty = match F::SIZE {
+        1 => "uint8_t",
+        2 => "uint16_t",
+        4 => "uint32_t",
+        _ => "uint64_t",
+    };
+    (ty, F::SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header() {
+        let header = generate();
+        assert!(header.contains("struct __attribute__((packed)) km_openat {\n    uint32_t id;\n    int64_t dirfd;\n    char path[KM_MAX_PATH_LEN];\n    uint32_t flags;\n    uint32_t mode;\n};"));
+        assert!(header.contains("    struct km_fault_spec fault;\n"));
+        assert!(header.contains("    uint32_t task;\n"));
+        assert!(header.contains("struct __attribute__((packed)) km_getcwd {\n    uint32_t id;\n};"));
+    }
+}
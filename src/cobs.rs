@@ -0,0 +1,197 @@
+//! A COBS frame is the COBS encoding of a message followed by a single zero
+//! byte, so frames can be found in a raw byte stream by splitting at zeros.
+//! Messages are the same as on other transports: a command message is the
+//! command id followed by the encoded command.
+
+use ::cobs::CobsEncoder;
+
+/// Maximum length of the COBS frame of a `len`-byte message, including the
+/// trailing zero.
+pub const fn max_frame_len(len: usize) -> usize {
+    len + len / 254 + 2
+}
+
+/// Encode the concatenation of `parts` as a COBS frame into `out`.
+/// Return the frame length, or `None` if `out` is too small.
+fn encode_parts(parts: &[&[u8]], out: &mut [u8]) -> Option<usize> {
+    let mut encoder = CobsEncoder::new(out);
+    for part in parts {
+        encoder.push(part).ok()?;
+    }
+    let len = match encoder.finalize().ok()? {
+        // The encoder emits nothing for an empty message.
+        0 => {
+            *out.first_mut()? = 1;
+            1
+        }
+        len => len,
+    };
+    *out.get_mut(len)? = 0;
+    Some(len + 1)
+}
+
+/// Encode `msg` as a COBS frame into `out`, e.g. an encoded response.
+/// Return the frame length, or `None` if `out` is too small.
+pub fn encode(msg: &[u8], out: &mut [u8]) -> Option<usize> {
+    encode_parts(&[msg], out)
+}
+
+/// Encode the command message of `cmd` with id `id` as a COBS frame into `out`.
+/// Return the frame length, or `None` if `out` is too small.
+pub fn encode_command(id: usize, cmd: &[u8], out: &mut [u8]) -> Option<usize> {
+    encode_parts(&[&crate::id_to_wire(id), cmd], out)
+}
+
+/// Decode a COBS frame in place, with or without its trailing zero.
+/// Return the message length, or `None` if the frame is malformed.
+pub fn decode_in_place(frame: &mut [u8]) -> Option<usize> {
+    let frame = match frame.split_last_mut() {
+        Some((0, frame)) => frame,
+        _ => frame,
+    };
+    ::cobs::decode_in_place(frame).ok()
+}
+
+#[cfg(feature = "std")]
+pub use self::stream::CobsTransport;
+
+#[cfg(feature = "std")]
+mod stream {
+    use super::{decode_in_place, encode_command, max_frame_len};
+    use crate::transport::{CommandSink, CommandSource};
+    use alloc::{vec, vec::Vec};
+    use std::io::{self, BufRead, BufReader, Read, Write};
+
+    /// Transport over a byte stream using zero-delimited COBS frames, suitable
+    /// for raw UART links.
+    pub struct CobsTransport<R, W> {
+        reader: BufReader<R>,
+        writer: W,
+        frame: Vec<u8>,
+    }
+
+    impl<R: Read, W: Write> CobsTransport<R, W> {
+        /// Create a transport from a reader and a writer.
+        pub fn new(reader: R, writer: W) -> Self {
+            Self {
+                reader: BufReader::new(reader),
+                writer,
+                frame: Vec::new(),
+            }
+        }
+    }
+
+    impl<R, W: Write> CommandSink for CobsTransport<R, W> {
+        type Error = io::Error;
+
+        fn send(&mut self, id: usize, cmd: &[u8]) -> io::Result<()> {
+            let mut frame = vec![0; max_frame_len(crate::ID_SIZE + cmd.len())];
+            let len = encode_command(id, cmd, &mut frame).unwrap();
+            self.writer.write_all(&frame[..len])?;
+            self.writer.flush()
+        }
+    }
+
+    impl<R: Read, W> CommandSource for CobsTransport<R, W> {
+        type Error = io::Error;
+
+        fn recv(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.frame.clear();
+            // Skip empty frames, e.g. zeros sent to resynchronize the link.
+            while self.frame.len() <= 1 {
+                self.frame.clear();
+                if self.reader.read_until(0, &mut self.frame)? == 0 {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+            }
+            let len = decode_in_place(&mut self.frame)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "bad COBS frame"))?;
+            if len > buf.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "response larger than buffer",
+                ));
+            }
+            buf[..len].copy_from_slice(&self.frame[..len]);
+            Ok(len)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let msg = [0, 1, 0, 0, 2, 3];
+        let mut frame = [0xff; max_frame_len(6)];
+        let len = encode(&msg, &mut frame).unwrap();
+        assert_eq!(frame[..len], [1, 2, 1, 1, 3, 2, 3, 0]);
+        assert!(!frame[..len - 1].contains(&0));
+        assert_eq!(decode_in_place(&mut frame[..len]), Some(6));
+        assert_eq!(frame[..6], msg);
+    }
+
+    #[test]
+    fn empty_message() {
+        let mut frame = [0xff; max_frame_len(0)];
+        assert_eq!(encode(&[], &mut frame), Some(2));
+        assert_eq!(frame, [1, 0]);
+        assert_eq!(decode_in_place(&mut frame), Some(0));
+    }
+
+    #[test]
+    fn long_message() {
+        let msg = [7; 600];
+        let mut frame = [0; max_frame_len(600)];
+        let len = encode(&msg, &mut frame).unwrap();
+        assert_eq!(len, frame.len());
+        assert_eq!(frame[len - 1], 0);
+        // Decoding without the trailing zero works as well.
+        assert_eq!(decode_in_place(&mut frame[..len - 1]), Some(600));
+        assert_eq!(frame[..600], msg);
+    }
+
+    #[test]
+    fn command() {
+        let mut frame = [0; max_frame_len(crate::ID_SIZE + 2)];
+        let len = encode_command(5, &[9, 0], &mut frame).unwrap();
+        assert_eq!(decode_in_place(&mut frame[..len]), Some(6));
+        assert_eq!(frame[..6], [5, 0, 0, 0, 9, 0]);
+    }
+
+    #[test]
+    fn errors() {
+        let mut small = [0; 3];
+        assert_eq!(encode(&[1, 2, 3], &mut small), None);
+        // The code byte points past the end of the frame.
+        let mut bad = [5, 1, 0];
+        assert_eq!(decode_in_place(&mut bad), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn transport() {
+        use crate::transport::{CommandSink, CommandSource};
+        use alloc::vec::Vec;
+
+        let mut written = Vec::new();
+        let mut sink = CobsTransport::new(std::io::empty(), &mut written);
+        sink.send(3, &[0, 1]).unwrap();
+        assert_eq!(written.last(), Some(&0));
+
+        let mut input = alloc::vec![0, 0];
+        input.extend_from_slice(&written);
+        let mut source = CobsTransport::new(&input[..], std::io::sink());
+        let mut buf = [0; 8];
+        assert_eq!(source.recv(&mut buf).unwrap(), 6);
+        assert_eq!(buf[..6], [3, 0, 0, 0, 0, 1]);
+        let err = source.recv(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+
+        let mut source = CobsTransport::new(&written[..], std::io::sink());
+        let err = source.recv(&mut buf[..4]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,202 @@
+#[cfg(feature = "checker")]
+use crate::fs::{diff_dirents, Dirent, DirentDiff};
+use crate::{
+    error::LinuxError,
+    fs::{stat_diff, LibcStat, StatFields},
+};
+
+/// Errors that kernels use interchangeably for permission failures.
+pub const PERMISSION_ERRNOS: &[LinuxError] = &[LinuxError::EACCES, LinuxError::EPERM];
+
+/// Errors POSIX allows for removing a non-empty directory.
+pub const NOT_EMPTY_ERRNOS: &[LinuxError] = &[LinuxError::ENOTEMPTY, LinuxError::EEXIST];
+
+/// Deviations between the kernel under test and the model that are
+/// acceptable, consumed by the comparison helpers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ComparePolicy {
+    /// Ignore access, modification and change times.
+    pub ignore_times: bool,
+    /// Ignore inode numbers.
+    pub ignore_inodes: bool,
+    /// Other stat fields to ignore.
+    pub ignored_stat_fields: StatFields,
+    /// Classes of errors considered equivalent.
+    pub errno_classes: &'static [&'static [LinuxError]],
+    /// Require directory entries in the same order.
+    pub dirent_order: bool,
+    /// Ignore `.` and `..` in directory listings.
+    pub skip_dots: bool,
+}
+
+impl ComparePolicy {
+    /// Require exact agreement.
+    pub const STRICT: Self = Self {
+        ignore_times: false,
+        ignore_inodes: false,
+        ignored_stat_fields: StatFields::empty(),
+        errno_classes: &[],
+        dirent_order: true,
+        skip_dots: false,
+    };
+
+    /// Tolerate differences that do not depend on the operations performed:
+    /// system-dependent stat fields, listing order, `.` and `..`, and
+    /// errors allowed interchangeably by POSIX.
+    pub const LENIENT: Self = Self {
+        ignore_times: true,
+        ignore_inodes: true,
+        ignored_stat_fields: StatFields::SYSTEM_DEPENDENT,
+        errno_classes: &[PERMISSION_ERRNOS, NOT_EMPTY_ERRNOS],
+        dirent_order: false,
+        skip_dots: true,
+    };
+
+    /// All stat fields ignored by the policy.
+    pub const fn ignored_stat(&self) -> StatFields {
+        let mut ignored = self.ignored_stat_fields;
+        if self.ignore_times {
+            ignored = ignored.union(StatFields::TIMES);
+        }
+        if self.ignore_inodes {
+            ignored = ignored.union(StatFields::INO);
+        }
+        ignored
+    }
+
+    /// Check if errors `a` and `b` are equivalent.
+    pub fn errno_eq(&self, a: LinuxError, b: LinuxError) -> bool {
+        a == b
+            || self
+                .errno_classes
+                .iter()
+                .any(|class| class.contains(&a) && class.contains(&b))
+    }
+
+    /// Check if syscall return values `a` and `b` agree, where negative
+    /// values are negated error codes.
+    pub fn ret_eq(&self, a: isize, b: isize) -> bool {
+        match (LinuxError::from_ret(a), LinuxError::from_ret(b)) {
+            (Some(a), Some(b)) => self.errno_eq(a, b),
+            _ => a == b,
+        }
+    }
+
+    /// Stat fields in which `a` and `b` differ, excluding ignored ones.
+    pub fn stat_diff(&self, a: &LibcStat, b: &LibcStat) -> StatFields {
+        stat_diff(a, b).difference(self.ignored_stat())
+    }
+
+    /// Compare the directory listing `actual` against `expected`.
+    #[cfg(feature = "checker")]
+    pub fn diff_dirents<'a>(&self, expected: &'a [Dirent], actual: &'a [Dirent]) -> DirentDiff<'a> {
+        let mut diff = diff_dirents(expected, actual, self.skip_dots, self.ignore_inodes);
+        if self.dirent_order && diff.is_empty() {
+            let keep = |entry: &&Dirent| !(self.skip_dots && entry.is_dot());
+            diff.reordered = !expected
+                .iter()
+                .filter(keep)
+                .map(|entry| &entry.name)
+                .eq(actual.iter().filter(keep).map(|entry| &entry.name));
+        }
+        diff
+    }
+}
+
+impl Default for ComparePolicy {
+    fn default() -> Self {
+        Self::LENIENT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use LinuxError::*;
+
+    #[test]
+    fn errno_classes() {
+        let strict = ComparePolicy::STRICT;
+        let lenient = ComparePolicy::default();
+        assert!(strict.errno_eq(EACCES, EACCES));
+        assert!(!strict.errno_eq(EACCES, EPERM));
+        assert!(lenient.errno_eq(EACCES, EPERM));
+        assert!(lenient.errno_eq(EEXIST, ENOTEMPTY));
+        assert!(!lenient.errno_eq(EPERM, ENOTEMPTY));
+        assert!(!lenient.errno_eq(ENOENT, EACCES));
+    }
+
+    #[test]
+    fn return_values() {
+        let lenient = ComparePolicy::LENIENT;
+        assert!(lenient.ret_eq(3, 3));
+        assert!(!lenient.ret_eq(3, 4));
+        assert!(lenient.ret_eq(-EACCES.code(), -EPERM.code()));
+        assert!(!ComparePolicy::STRICT.ret_eq(-EACCES.code(), -EPERM.code()));
+        assert!(!lenient.ret_eq(0, -ENOENT.code()));
+    }
+
+    #[test]
+    fn stat_fields() {
+        assert_eq!(ComparePolicy::STRICT.ignored_stat(), StatFields::empty());
+        let policy = ComparePolicy {
+            ignore_times: true,
+            ignore_inodes: true,
+            ignored_stat_fields: StatFields::UID,
+            ..ComparePolicy::STRICT
+        };
+        assert_eq!(
+            policy.ignored_stat(),
+            StatFields::TIMES | StatFields::INO | StatFields::UID
+        );
+        let mut a = LibcStat::default();
+        (a.ino, a.uid, a.size, a.mtime_sec) = (1, 1, 1, 1);
+        let b = LibcStat::default();
+        assert_eq!(policy.stat_diff(&a, &b), StatFields::SIZE);
+        assert_eq!(
+            ComparePolicy::LENIENT.stat_diff(&a, &b),
+            StatFields::UID | StatFields::SIZE
+        );
+    }
+
+    #[cfg(feature = "checker")]
+    #[test]
+    fn dirent_order() {
+        use crate::fs::FileKind;
+
+        let dirent = |name: &str| Dirent {
+            ino: 1,
+            kind: FileKind::File,
+            name: name.try_into().unwrap(),
+        };
+        let expected = [dirent("."), dirent("a"), dirent("b")];
+        let actual = [dirent("b"), dirent("a")];
+        let strict = ComparePolicy::STRICT;
+        let ordered = ComparePolicy {
+            skip_dots: true,
+            ..strict
+        };
+        assert!(ComparePolicy::LENIENT
+            .diff_dirents(&expected, &actual)
+            .is_empty());
+        assert!(ordered.diff_dirents(&expected, &actual).reordered);
+        assert!(ordered.diff_dirents(&expected, &expected[1..]).is_empty());
+        // Listings differing only by inode numbers differ under STRICT.
+        let renumbered = [
+            Dirent {
+                ino: 2,
+                ..dirent("a")
+            },
+            dirent("b"),
+        ];
+        let diff = strict.diff_dirents(&expected[1..], &renumbered);
+        assert_eq!(diff.ino_mismatch, [(&expected[1], &renumbered[0])]);
+        assert!(!diff.is_empty());
+        let lenient = ComparePolicy::LENIENT;
+        assert!(lenient.diff_dirents(&expected[1..], &renumbered).is_empty());
+        // Missing entries are reported instead of the order.
+        let diff = strict.diff_dirents(&expected, &actual);
+        assert_eq!(diff.missing, [&expected[0]]);
+        assert!(!diff.reordered);
+    }
+}
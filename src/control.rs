@@ -0,0 +1,338 @@
+use crate::{
+    command, envelope::TaskId, error::LinuxError, fs::MAX_PATH_LEN, response, size::MaxSize,
+    validate::Validate, COMMANDS, MAX_COMMAND_SIZE,
+};
+use core::ops::Range;
+use serde::{Deserialize, Serialize};
+
+/// Max number of command ids in [`Capabilities`].
+pub const MAX_SUPPORTED_COMMANDS: usize = 128;
+
+/// Max number of bitmap bytes in one [`Coverage`] response.
+pub const MAX_COVERAGE_BYTES: usize = 1024;
+
+/// Max number of failures in an [`InvariantReport`].
+pub const MAX_INVARIANT_FAILURES: usize = 8;
+
+/// Max length of the message of an [`InvariantFailure`].
+pub const MAX_FAILURE_MESSAGE_LEN: usize = 128;
+
+/// Command ids reserved for control commands, which are handled by the
+/// harness itself instead of being forwarded to the kernel under test.
+/// [`Nop`](crate::Nop) also lies in this range.
+pub const CONTROL_IDS: Range<usize> = 0xf000..0x10000;
+
+/// Check if `id` is reserved for control commands.
+pub fn is_control_id(id: usize) -> bool {
+    CONTROL_IDS.contains(&id)
+}
+
+command!(
+    /// Probe the liveness of the harness, which echoes `nonce` back.
+    struct Ping {
+        /// Arbitrary value to be echoed.
+        nonce: u64,
+    },
+    0xf000,
+    Pong
+);
+
+command!(
+    /// Reset the harness state (e.g. close files opened by previous
+    /// commands) between test cases.
+    struct Reset {},
+    0xf001
+);
+
+command!(
+    /// Query the features supported by the harness.
+    struct GetCapabilities {},
+    0xf002,
+    Capabilities
+);
+
+command!(
+    /// Stop the harness and, if possible, power off the target cleanly.
+    struct ShutdownHarness {},
+    0xf003
+);
+
+command!(
+    /// Inject a fault into the kernel under test, so that error paths can be
+    /// explored systematically.
+    struct InjectFault {
+        /// The fault to inject.
+        fault: FaultSpec,
+    },
+    0xf004
+);
+
+command!(
+    /// Query the code coverage an instrumented kernel reached since the last
+    /// reset. The coverage bitmap may be larger than one response, so it is
+    /// read in chunks starting at `offset`.
+    struct QueryCoverage {
+        /// Byte offset into the coverage bitmap.
+        offset: u32,
+        /// Clear the coverage after reading the last chunk.
+        reset: bool,
+    },
+    0xf005,
+    Coverage
+);
+
+command!(
+    /// Seed the randomized behavior of the harness (e.g. address selection
+    /// for mmap hints), so that replays of a recorded trace are reproducible.
+    struct SetSeed {
+        /// The seed.
+        seed: u64,
+    },
+    0xf006
+);
+
+command!(
+    /// Make `task` the current task of the harness, which executes all
+    /// following commands not directed to a task by their envelope.
+    struct SwitchTask {
+        /// The task to switch to.
+        task: TaskId,
+    },
+    0xf007
+);
+
+command!(
+    /// Run internal consistency checks of the kernel under test and report
+    /// the failed ones, so the checker can trigger self-checks at chosen
+    /// points of a sequence.
+    struct AssertInvariant {
+        /// The invariants to check.
+        kind: InvariantKind,
+    },
+    0xf008,
+    InvariantReport
+);
+
+impl Validate for Ping {}
+
+impl Validate for Reset {}
+
+impl Validate for GetCapabilities {}
+
+impl Validate for ShutdownHarness {}
+
+impl Validate for InjectFault {}
+
+impl Validate for QueryCoverage {}
+
+impl Validate for SetSeed {}
+
+impl Validate for SwitchTask {}
+
+impl Validate for AssertInvariant {}
+
+/// Fault to be injected by [`InjectFault`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FaultSpec {
+    /// Fail the next `count` kernel memory allocations.
+    FailAlloc {
+        /// Number of allocations to fail.
+        count: u32,
+    },
+    /// Make the next `count` invocations of the syscall with command id `id`
+    /// fail with `errno`.
+    FailSyscall {
+        /// Command id of the syscall.
+        id: usize,
+        /// Positive errno value to return.
+        errno: isize,
+        /// Number of invocations to fail.
+        count: u32,
+    },
+    /// Make the next `count` block device reads fail with `EIO`.
+    FailRead {
+        /// Number of reads to fail.
+        count: u32,
+    },
+    /// Make the next `count` block device writes fail with `EIO`.
+    FailWrite {
+        /// Number of writes to fail.
+        count: u32,
+    },
+    /// Remove all pending faults.
+    Clear,
+}
+
+impl FaultSpec {
+    /// Fail the next `count` invocations of the syscall with command id `id`
+    /// with `err`.
+    pub fn fail_syscall(id: usize, err: LinuxError, count: u32) -> Self {
+        FaultSpec::FailSyscall {
+            id,
+            errno: err as isize,
+            count,
+        }
+    }
+}
+
+impl MaxSize for FaultSpec {
+    // Variant index followed by the largest variant, `FailSyscall`.
+    const MAX_SIZE: usize = u32::MAX_SIZE + usize::MAX_SIZE + isize::MAX_SIZE + u32::MAX_SIZE;
+}
+
+/// Invariant to be checked by [`AssertInvariant`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InvariantKind {
+    /// All invariants the harness knows.
+    All,
+    /// Page tables agree with the VMA list of every task.
+    PageTables,
+    /// Reference counts of open file descriptions match the fd tables.
+    FdRefcounts,
+    /// Allocator metadata is consistent.
+    Allocator,
+    /// Harness-specific invariant.
+    Custom(u32),
+}
+
+impl MaxSize for InvariantKind {
+    // Variant index followed by the largest variant, `Custom`.
+    const MAX_SIZE: usize = u32::MAX_SIZE + u32::MAX_SIZE;
+}
+
+/// A failed invariant check.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct InvariantFailure {
+    /// The violated invariant.
+    pub kind: InvariantKind,
+    /// Description of the violation, truncated to fit.
+    pub message: heapless::String<MAX_FAILURE_MESSAGE_LEN>,
+}
+
+impl InvariantFailure {
+    /// Create a failure of `kind`, truncating `message` to fit.
+    pub fn new(kind: InvariantKind, message: &str) -> Self {
+        let mut end = message.len().min(MAX_FAILURE_MESSAGE_LEN);
+        while !message.is_char_boundary(end) {
+            end -= 1;
+        }
+        let mut truncated = heapless::String::new();
+        // `end` is at most the capacity.
+        truncated.push_str(&message[..end]).unwrap();
+        Self {
+            kind,
+            message: truncated,
+        }
+    }
+}
+
+response!(
+    /// Result of [`Ping`].
+    struct Pong {
+        /// The nonce of the ping.
+        nonce: u64,
+    }
+);
+
+response!(
+    /// Result of [`GetCapabilities`], returned by the harness on handshake so
+    /// that the checker only generates commands the target implements.
+    struct Capabilities {
+        /// Ids of the commands the harness implements.
+        commands: heapless::Vec<usize, MAX_SUPPORTED_COMMANDS>,
+        /// Max path length the harness accepts.
+        max_path_len: usize,
+        /// Max size of an encoded command the harness can receive.
+        max_command_size: usize,
+    }
+);
+
+impl Capabilities {
+    /// Capabilities of a harness implementing every command in [`COMMANDS`]
+    /// with the limits of this crate.
+    pub fn all() -> Self {
+        let mut commands = heapless::Vec::new();
+        for desc in COMMANDS {
+            if !commands.contains(&desc.id) {
+                // `MAX_SUPPORTED_COMMANDS` is larger than the number of commands.
+                commands.push(desc.id).unwrap();
+            }
+        }
+        Self::new(commands, MAX_PATH_LEN, MAX_COMMAND_SIZE)
+    }
+
+    /// Check if the harness implements the command `id`.
+    pub fn supports(&self, id: usize) -> bool {
+        self.commands.contains(&id)
+    }
+}
+
+response!(
+    /// Result of [`QueryCoverage`]: a chunk of the edge coverage bitmap, where
+    /// bit `i` of byte `j` is set if edge `8 * (offset + j) + i` was hit.
+    struct Coverage {
+        /// Byte offset of the chunk in the bitmap.
+        offset: u32,
+        /// Total size of the bitmap in bytes.
+        total: u32,
+        /// The chunk.
+        bitmap: heapless::Vec<u8, MAX_COVERAGE_BYTES>,
+    }
+);
+
+impl Coverage {
+    /// Check if this is the last chunk of the bitmap.
+    pub fn is_last(&self) -> bool {
+        self.offset as usize + self.bitmap.len() >= self.total as usize
+    }
+
+    /// Check if `edge` was hit. Return `false` if it is outside this chunk.
+    pub fn is_hit(&self, edge: usize) -> bool {
+        let Some(byte) = (edge / 8).checked_sub(self.offset as usize) else {
+            return false;
+        };
+        self.bitmap
+            .get(byte)
+            .is_some_and(|bits| bits & (1 << (edge % 8)) != 0)
+    }
+
+    /// Number of edges hit in this chunk.
+    pub fn count(&self) -> usize {
+        self.bitmap
+            .iter()
+            .map(|bits| bits.count_ones() as usize)
+            .sum()
+    }
+
+    /// Merge this chunk into the accumulated bitmap `global`, return the
+    /// number of edges not hit before. Bytes beyond `global` are ignored.
+    pub fn merge_into(&self, global: &mut [u8]) -> usize {
+        let start = (self.offset as usize).min(global.len());
+        global[start..]
+            .iter_mut()
+            .zip(&self.bitmap)
+            .map(|(acc, &bits)| {
+                let new = bits & !*acc;
+                *acc |= bits;
+                new.count_ones() as usize
+            })
+            .sum()
+    }
+}
+
+response!(
+    /// Result of [`AssertInvariant`].
+    struct InvariantReport {
+        /// Number of invariants checked.
+        checked: u32,
+        /// The failed checks, at most [`MAX_INVARIANT_FAILURES`] of them.
+        failures: heapless::Vec<InvariantFailure, MAX_INVARIANT_FAILURES>,
+    }
+);
+
+impl InvariantReport {
+    /// Check if all invariants hold.
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
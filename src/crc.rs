@@ -0,0 +1,127 @@
+use crate::VersionedDecoder;
+#[cfg(feature = "checker")]
+use alloc::vec::Vec;
+
+/// Reflected polynomial of CRC-32/ISO-HDLC, the CRC used by zlib and Ethernet.
+const POLY: u32 = 0xedb8_8320;
+
+const TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ POLY
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Size of the checksum appended to a frame.
+pub const CRC_SIZE: usize = core::mem::size_of::<u32>();
+
+/// Compute the CRC-32/ISO-HDLC checksum of `data`.
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, &byte| {
+        TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Append the little-endian checksum of `frame` to it.
+#[cfg(feature = "checker")]
+pub fn append_crc32(mut frame: Vec<u8>) -> Vec<u8> {
+    let crc = crc32(&frame);
+    frame.extend_from_slice(&crc.to_le_bytes());
+    frame
+}
+
+/// Split a versioned command frame followed by its checksum off `data`.
+///
+/// Return the frame and the data after the checksum, or `None` if the
+/// frame is malformed or the checksum does not match.
+pub fn split_frame(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (_, rest) = VersionedDecoder::new(data)?;
+    let (frame, rest) = data.split_at(data.len() - rest.len());
+    if rest.len() < CRC_SIZE {
+        return None;
+    }
+    let (crc, rest) = rest.split_at(CRC_SIZE);
+    if crc32(frame).to_le_bytes() != crc {
+        return None;
+    }
+    Some((frame, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frame of `Close { fd: 3 }`: version 1, length 1, zigzag-encoded fd.
+    const CLOSE: [u8; 3] = [1, 1, 6];
+
+    fn framed() -> [u8; 7] {
+        let mut data = [0; 7];
+        data[..3].copy_from_slice(&CLOSE);
+        data[3..].copy_from_slice(&crc32(&CLOSE).to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn known_answer() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414f_a339
+        );
+    }
+
+    #[test]
+    fn split() {
+        let mut data = [0; 8];
+        data[..7].copy_from_slice(&framed());
+        data[7] = 0xaa;
+        assert_eq!(split_frame(&data), Some((&CLOSE[..], &[0xaa][..])));
+        assert_eq!(split_frame(&data[..6]), None);
+        assert_eq!(split_frame(&data[..2]), None);
+    }
+
+    #[test]
+    fn corrupted() {
+        for i in 0..7 {
+            let mut data = framed();
+            data[i] ^= 0x10;
+            assert_eq!(split_frame(&data), None, "byte {} flipped", i);
+        }
+    }
+
+    #[cfg(feature = "checker")]
+    #[test]
+    fn encode() {
+        let cmd = crate::fs::Close::new(3);
+        let data = cmd.to_bytes_crc32();
+        assert_eq!(data, append_crc32(cmd.to_bytes()));
+        assert_eq!(data, framed());
+    }
+
+    #[cfg(feature = "harness")]
+    #[test]
+    fn decode() {
+        use crate::fs::Close;
+
+        let data = framed();
+        let (cmd, rest) = Close::from_bytes_crc32(&data).unwrap();
+        assert_eq!((cmd.fd, rest), (3, &[][..]));
+        let mut data = framed();
+        data[2] ^= 1;
+        assert!(Close::from_bytes_crc32(&data).is_none());
+    }
+}
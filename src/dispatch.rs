@@ -0,0 +1,87 @@
+use core::fmt;
+
+/// Error returned by [`dispatch!`](crate::dispatch) when no handler runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DispatchError {
+    /// No handler is given for the command id.
+    UnknownId(usize),
+    /// The command data could not be decoded.
+    Malformed(usize),
+}
+
+impl fmt::Display for DispatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownId(id) => write!(f, "unknown command id {}", id),
+            Self::Malformed(id) => write!(f, "malformed command with id {}", id),
+        }
+    }
+}
+
+/// Call `handler` with `cmd`, so that the closure argument type is inferred.
+#[doc(hidden)]
+pub fn __call<C, R, F: FnOnce(C) -> R>(cmd: C, handler: F) -> R {
+    handler(cmd)
+}
+
+/// Decode the command with id `$id` from `$data` and run its handler.
+///
+/// Evaluate to `Ok` with the result of the handler, or to `Err` with a
+/// [`DispatchError`] if no handler is given for the id or the data is
+/// malformed. If several commands share an id, the first one listed wins.
+///
+/// Format: `dispatch!($id, $data, { $cmd => $handler, ... })`, where each
+/// handler is a closure taking the decoded command.
+///
+/// ```ignore
+/// let (id, data) = id_from_bytes(msg)?;
+/// let ret = dispatch!(id, data, {
+///     Openat => |cmd| kernel.openat(cmd.dirfd, &cmd.path, cmd.flags, cmd.mode),
+///     Close => |cmd| kernel.close(cmd.fd),
+/// });
+/// ```
+#[cfg(feature = "harness")]
+#[macro_export]
+macro_rules! dispatch {
+    ($id:expr, $data:expr, { $($cmd:ty => $handler:expr),* $(,)? }) => {{
+        let id: usize = $id;
+        let data: &[u8] = $data;
+        match id {
+            $(
+                id if id == <$cmd>::ID => match <$cmd>::from_bytes(data) {
+                    Some((cmd, _)) => Ok($crate::dispatch::__call::<$cmd, _, _>(cmd, $handler)),
+                    None => Err($crate::dispatch::DispatchError::Malformed(id)),
+                },
+            )*
+            id => Err($crate::dispatch::DispatchError::UnknownId(id)),
+        }
+    }};
+}
+
+#[cfg(all(test, feature = "harness"))]
+mod tests {
+    use super::*;
+    use crate::fs::{Close, Getcwd};
+
+    fn run(id: usize, data: &[u8]) -> Result<isize, DispatchError> {
+        crate::dispatch!(id, data, {
+            Close => |cmd| cmd.fd,
+            Getcwd => |_| -1,
+        })
+    }
+
+    #[cfg(feature = "postcard")]
+    #[test]
+    fn dispatch() {
+        // `Close { fd: 3 }`: version 1, length 1, zigzag-encoded fd.
+        assert_eq!(run(Close::ID, &[1, 1, 6]), Ok(3));
+        assert_eq!(
+            run(usize::MAX, &[1, 1, 6]),
+            Err(DispatchError::UnknownId(usize::MAX))
+        );
+        assert_eq!(
+            run(Close::ID, &[1, 1]),
+            Err(DispatchError::Malformed(Close::ID))
+        );
+    }
+}
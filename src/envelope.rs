@@ -0,0 +1,142 @@
+use crate::size::MaxSize;
+#[cfg(feature = "checker")]
+use alloc::vec::Vec;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Identifier of a task (process or thread) in the kernel under test, as
+/// assigned by the harness, e.g. in creation order.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct TaskId(pub u32);
+
+impl MaxSize for TaskId {
+    const MAX_SIZE: usize = u32::MAX_SIZE;
+}
+
+/// Per-command metadata, sent between the command id and the command when
+/// both sides agree to use envelopes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Envelope {
+    /// Time limit in milliseconds the harness should honor when executing
+    /// the command, or `None` for no limit. A harness that gives up replies
+    /// with [`Reply::TimedOut`].
+    pub timeout_ms: Option<u32>,
+    /// Task that should execute the command, or `None` for the current task
+    /// of the harness (see `SwitchTask`).
+    pub task: Option<TaskId>,
+}
+
+impl MaxSize for Envelope {
+    const MAX_SIZE: usize = 1 + u32::MAX_SIZE + 1 + TaskId::MAX_SIZE;
+}
+
+impl Envelope {
+    /// Create an envelope with a timeout.
+    pub fn with_timeout(timeout_ms: u32) -> Self {
+        Self {
+            timeout_ms: Some(timeout_ms),
+            ..Default::default()
+        }
+    }
+
+    /// Create an envelope directing the command to `task`.
+    pub fn for_task(task: TaskId) -> Self {
+        Self {
+            task: Some(task),
+            ..Default::default()
+        }
+    }
+
+    /// Serialize the envelope into a byte array.
+    #[cfg(all(feature = "checker", feature = "postcard"))]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        postcard::to_allocvec(self).unwrap()
+    }
+
+    /// Deserialize the envelope from a byte array, return the envelope and the remaining data.
+    #[cfg(feature = "postcard")]
+    pub fn from_bytes(data: &[u8]) -> Option<(Self, &[u8])> {
+        postcard::take_from_bytes(data).ok()
+    }
+}
+
+/// Reply of the harness to a command sent with an [`Envelope`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Reply<T> {
+    /// The command completed with the given response.
+    Done(T),
+    /// The command did not complete within its timeout.
+    TimedOut,
+}
+
+impl<T: Serialize + DeserializeOwned> Reply<T> {
+    /// Serialize the reply into a byte array.
+    #[cfg(all(feature = "checker", feature = "postcard"))]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        postcard::to_allocvec(self).unwrap()
+    }
+
+    /// Serialize the reply into `buf` without allocating, return the used
+    /// part of `buf`, or `None` if `buf` is too small.
+    #[cfg(feature = "postcard")]
+    pub fn to_slice<'b>(&self, buf: &'b mut [u8]) -> Option<&'b mut [u8]> {
+        postcard::to_slice(self, buf).ok()
+    }
+
+    /// Deserialize the reply from a byte array, return the reply and the remaining data.
+    #[cfg(feature = "postcard")]
+    pub fn from_bytes(data: &[u8]) -> Option<(Self, &[u8])> {
+        postcard::take_from_bytes(data).ok()
+    }
+}
+
+#[cfg(all(test, feature = "postcard"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn envelope_roundtrip() {
+        let envelopes = [
+            Envelope::default(),
+            Envelope::with_timeout(u32::MAX),
+            Envelope::for_task(TaskId(7)),
+            Envelope {
+                timeout_ms: Some(100),
+                task: Some(TaskId(u32::MAX)),
+            },
+        ];
+        for envelope in envelopes {
+            let mut buf = [0; Envelope::MAX_SIZE + 1];
+            let len = postcard::to_slice(&envelope, &mut buf).unwrap().len();
+            assert!(len <= Envelope::MAX_SIZE);
+            buf[len] = 0xaa;
+            assert_eq!(
+                Envelope::from_bytes(&buf[..len + 1]),
+                Some((envelope, &[0xaa][..]))
+            );
+            #[cfg(feature = "checker")]
+            assert_eq!(envelope.to_bytes(), buf[..len]);
+        }
+        assert_eq!(Envelope::from_bytes(&[1]), None);
+    }
+
+    #[test]
+    fn reply_roundtrip() {
+        let mut buf = [0; 8];
+        let done = Reply::Done(-2i32);
+        let bytes = done.to_slice(&mut buf).unwrap();
+        assert_eq!(Reply::from_bytes(bytes), Some((done.clone(), &[][..])));
+        #[cfg(feature = "checker")]
+        assert_eq!(done.to_bytes(), bytes);
+
+        let bytes = Reply::<i32>::TimedOut.to_slice(&mut buf).unwrap();
+        assert_eq!(
+            Reply::<i32>::from_bytes(bytes),
+            Some((Reply::TimedOut, &[][..]))
+        );
+        assert!(Reply::<i32>::from_bytes(&[0]).is_none());
+        assert!(Reply::<i32>::from_bytes(&[2]).is_none());
+        assert!(Reply::Done(u64::MAX).to_slice(&mut buf[..4]).is_none());
+    }
+}
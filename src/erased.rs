@@ -0,0 +1,111 @@
+use crate::Encode;
+use alloc::{boxed::Box, vec::Vec};
+use core::{any::Any, fmt};
+
+/// Object-safe view of a command, so that queues, schedulers and minimizers
+/// can hold commands of different types.
+///
+/// Implemented for every command defined by [`command!`](crate::command).
+pub trait ErasedCommand: Any {
+    /// Command id.
+    fn id(&self) -> usize;
+
+    /// Command name.
+    fn name(&self) -> &'static str;
+
+    /// Append the encoded command to `buf`.
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    /// The command as a `Debug` value.
+    fn debug(&self) -> &dyn fmt::Debug;
+
+    /// Clone the command into a new box.
+    fn clone_box(&self) -> AnyCommand;
+
+    /// The command as `Any`, for downcasting.
+    fn as_any(&self) -> &dyn Any;
+}
+
+/// A boxed command of any type.
+pub type AnyCommand = Box<dyn ErasedCommand>;
+
+impl<C: Encode + fmt::Debug + Clone + 'static> ErasedCommand for C {
+    fn id(&self) -> usize {
+        C::ID
+    }
+
+    fn name(&self) -> &'static str {
+        C::NAME
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.encode_into(buf)
+    }
+
+    fn debug(&self) -> &dyn fmt::Debug {
+        self
+    }
+
+    fn clone_box(&self) -> AnyCommand {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl dyn ErasedCommand {
+    /// Serialize the command into a byte array.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.encode(&mut bytes);
+        bytes
+    }
+
+    /// The command as a `C`, if it is one.
+    pub fn downcast_ref<C: 'static>(&self) -> Option<&C> {
+        self.as_any().downcast_ref()
+    }
+}
+
+impl fmt::Debug for dyn ErasedCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.debug().fmt(f)
+    }
+}
+
+impl Clone for AnyCommand {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fs::Close, mem::Munmap};
+    use alloc::format;
+
+    #[test]
+    fn downcast() {
+        let cmd: AnyCommand = Box::new(Close::new(3));
+        assert_eq!((cmd.id(), cmd.name()), (Close::ID, Close::NAME));
+        assert_eq!(cmd.downcast_ref::<Close>(), Some(&Close::new(3)));
+        assert!(cmd.downcast_ref::<Munmap>().is_none());
+        assert_eq!(format!("{:?}", cmd), format!("{:?}", Close::new(3)));
+
+        let copy = cmd.clone();
+        assert_eq!(copy.downcast_ref::<Close>(), Some(&Close::new(3)));
+        assert_eq!(copy.to_bytes(), Close::new(3).to_bytes());
+    }
+
+    #[test]
+    fn mixed() {
+        let cmds: [AnyCommand; 2] = [Box::new(Close::new(3)), Box::new(Munmap::new(0x1000, 1))];
+        let ids: Vec<usize> = cmds.iter().map(|cmd| cmd.id()).collect();
+        assert_eq!(ids, [Close::ID, Munmap::ID]);
+        assert_eq!(cmds[1].name(), Munmap::NAME);
+        assert_eq!(cmds[1].downcast_ref::<Munmap>().map(|cmd| cmd.len), Some(1));
+    }
+}
@@ -0,0 +1,361 @@
+//! Fixed-size records: every command is encoded as its id followed by its
+//! fields at fixed widths, padded with zeros to [`RECORD_SIZE`] bytes. A
+//! harness can read commands into a single DMA buffer, find the id at
+//! offset 0 and every field at a constant offset, without parsing.
+//!
+//! Fields are packed in declaration order, without alignment padding:
+//!
+//! - integers and flags are little-endian at their own width, `usize` and
+//!   `isize` at 64 bits, and `bool` as one byte;
+//! - paths take [`MAX_PATH_LEN`] bytes, zero-padded, so a path of the
+//!   maximum length has no NUL terminator;
+//! - [`InvariantKind`] is a `u32` variant index followed by the `u32` of
+//!   `Custom`, zero otherwise;
+//! - [`FaultSpec`] is a `u32` variant index, the `u32` count, the `u64`
+//!   command id and the `i64` errno, unused fields being zero.
+//!
+//! Every command of a given id thus has a constant byte layout, described
+//! for C by the `cheader` module.
+
+use crate::{
+    control::{FaultSpec, InvariantKind},
+    envelope::TaskId,
+    fs::{FileMode, OpenFlags, Path, UnlinkatFlags, MAX_PATH_LEN},
+    id_from_wire, id_to_wire,
+    mem::{MmapFlags, ProtFlags},
+    ID_SIZE,
+};
+use core::str;
+
+/// A field type with a fixed-width encoding.
+pub trait FixedField: Sized {
+    /// Encoded size in bytes.
+    const SIZE: usize;
+
+    /// Encode the value into `buf`, which is `SIZE` bytes long.
+    fn write_fixed(&self, buf: &mut [u8]);
+
+    /// Decode a value from `buf`, which is `SIZE` bytes long, or return
+    /// `None` if it holds no valid value.
+    fn read_fixed(buf: &[u8]) -> Option<Self>;
+}
+
+macro_rules! impl_fixed_int {
+    ($($t:ty),*) => {
+        $(
+            impl FixedField for $t {
+                const SIZE: usize = core::mem::size_of::<$t>();
+
+                fn write_fixed(&self, buf: &mut [u8]) {
+                    buf.copy_from_slice(&self.to_le_bytes());
+                }
+
+                fn read_fixed(buf: &[u8]) -> Option<Self> {
+                    Some(<$t>::from_le_bytes(buf.try_into().ok()?))
+                }
+            }
+        )*
+    };
+}
+
+impl_fixed_int!(u8, i8, u16, i16, u32, i32, u64, i64);
+
+impl FixedField for usize {
+    const SIZE: usize = u64::SIZE;
+
+    fn write_fixed(&self, buf: &mut [u8]) {
+        (*self as u64).write_fixed(buf)
+    }
+
+    fn read_fixed(buf: &[u8]) -> Option<Self> {
+        u64::read_fixed(buf)?.try_into().ok()
+    }
+}
+
+impl FixedField for isize {
+    const SIZE: usize = i64::SIZE;
+
+    fn write_fixed(&self, buf: &mut [u8]) {
+        (*self as i64).write_fixed(buf)
+    }
+
+    fn read_fixed(buf: &[u8]) -> Option<Self> {
+        i64::read_fixed(buf)?.try_into().ok()
+    }
+}
+
+impl FixedField for bool {
+    const SIZE: usize = 1;
+
+    fn write_fixed(&self, buf: &mut [u8]) {
+        buf[0] = *self as u8;
+    }
+
+    fn read_fixed(buf: &[u8]) -> Option<Self> {
+        match buf[0] {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! impl_fixed_flags {
+    ($($t:ty: $bits:ty),*) => {
+        $(
+            impl FixedField for $t {
+                const SIZE: usize = <$bits>::SIZE;
+
+                fn write_fixed(&self, buf: &mut [u8]) {
+                    self.bits().write_fixed(buf)
+                }
+
+                fn read_fixed(buf: &[u8]) -> Option<Self> {
+                    Some(<$t>::from_bits_truncate(<$bits>::read_fixed(buf)?))
+                }
+            }
+        )*
+    };
+}
+
+impl_fixed_flags!(
+    OpenFlags: u32,
+    FileMode: u32,
+    UnlinkatFlags: u32,
+    ProtFlags: u8,
+    MmapFlags: u32
+);
+
+impl FixedField for TaskId {
+    const SIZE: usize = u32::SIZE;
+
+    fn write_fixed(&self, buf: &mut [u8]) {
+        self.0.write_fixed(buf)
+    }
+
+    fn read_fixed(buf: &[u8]) -> Option<Self> {
+        u32::read_fixed(buf).map(TaskId)
+    }
+}
+
+impl FixedField for Path {
+    const SIZE: usize = MAX_PATH_LEN;
+
+    fn write_fixed(&self, buf: &mut [u8]) {
+        buf[..self.len()].copy_from_slice(self.as_bytes());
+        buf[self.len()..].fill(0);
+    }
+
+    fn read_fixed(buf: &[u8]) -> Option<Self> {
+        let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+        Path::try_from(str::from_utf8(&buf[..len]).ok()?).ok()
+    }
+}
+
+impl FixedField for InvariantKind {
+    const SIZE: usize = 2 * u32::SIZE;
+
+    fn write_fixed(&self, buf: &mut [u8]) {
+        let (index, custom) = match *self {
+            InvariantKind::All => (0, 0),
+            InvariantKind::PageTables => (1, 0),
+            InvariantKind::FdRefcounts => (2, 0),
+            InvariantKind::Allocator => (3, 0),
+            InvariantKind::Custom(custom) => (4, custom),
+        };
+        let (head, tail) = buf.split_at_mut(u32::SIZE);
+        (index as u32).write_fixed(head);
+        custom.write_fixed(tail);
+    }
+
+    fn read_fixed(buf: &[u8]) -> Option<Self> {
+        let (head, tail) = buf.split_at(u32::SIZE);
+        Some(match u32::read_fixed(head)? {
+            0 => InvariantKind::All,
+            1 => InvariantKind::PageTables,
+            2 => InvariantKind::FdRefcounts,
+            3 => InvariantKind::Allocator,
+            4 => InvariantKind::Custom(u32::read_fixed(tail)?),
+            _ => return None,
+        })
+    }
+}
+
+impl FixedField for FaultSpec {
+    const SIZE: usize = 2 * u32::SIZE + u64::SIZE + i64::SIZE;
+
+    fn write_fixed(&self, buf: &mut [u8]) {
+        let (index, count, id, errno) = match *self {
+            FaultSpec::FailAlloc { count } => (0, count, 0, 0),
+            FaultSpec::FailSyscall { id, errno, count } => (1, count, id, errno),
+            FaultSpec::FailRead { count } => (2, count, 0, 0),
+            FaultSpec::FailWrite { count } => (3, count, 0, 0),
+            FaultSpec::Clear => (4, 0, 0, 0),
+        };
+        let buf = __write_field(&(index as u32), buf);
+        let buf = __write_field(&count, buf);
+        let buf = __write_field(&id, buf);
+        __write_field(&errno, buf);
+    }
+
+    fn read_fixed(buf: &[u8]) -> Option<Self> {
+        let (index, buf) = __read_field::<u32>(buf)?;
+        let (count, buf) = __read_field::<u32>(buf)?;
+        let (id, buf) = __read_field::<usize>(buf)?;
+        let (errno, _) = __read_field::<isize>(buf)?;
+        Some(match index {
+            0 => FaultSpec::FailAlloc { count },
+            1 => FaultSpec::FailSyscall { id, errno, count },
+            2 => FaultSpec::FailRead { count },
+            3 => FaultSpec::FailWrite { count },
+            4 => FaultSpec::Clear,
+            _ => return None,
+        })
+    }
+}
+
+/// Size of every record, large enough for any command.
+pub const RECORD_SIZE: usize = ID_SIZE + crate::MAX_FIXED_SIZE;
+
+/// A fixed-size record holding one command.
+pub type Record = [u8; RECORD_SIZE];
+
+/// Write `id` at the start of `record`, return the space left for the fields.
+#[doc(hidden)]
+pub fn __write_id(record: &mut Record, id: usize) -> &mut [u8] {
+    record[..ID_SIZE].copy_from_slice(&id_to_wire(id));
+    &mut record[ID_SIZE..]
+}
+
+/// Encode `value` at the start of `buf`, return the rest of `buf`.
+#[doc(hidden)]
+pub fn __write_field<'b, T: FixedField>(value: &T, buf: &'b mut [u8]) -> &'b mut [u8] {
+    let (head, rest) = buf.split_at_mut(T::SIZE);
+    value.write_fixed(head);
+    rest
+}
+
+/// Decode a value from the start of `buf`, return it and the rest of `buf`.
+#[doc(hidden)]
+pub fn __read_field<T: FixedField>(buf: &[u8]) -> Option<(T, &[u8])> {
+    let (head, rest) = buf.split_at_checked(T::SIZE)?;
+    Some((T::read_fixed(head)?, rest))
+}
+
+/// Split the first record off `data`, return the command id, the fields
+/// (including padding) and the remaining data, or `None` if `data` is
+/// shorter than a record.
+pub fn split_record(data: &[u8]) -> Option<(usize, &[u8], &[u8])> {
+    if data.len() < RECORD_SIZE {
+        return None;
+    }
+    let (record, rest) = data.split_at(RECORD_SIZE);
+    let (id, fields) = record.split_at(ID_SIZE);
+    Some((id_from_wire(id.try_into().unwrap()), fields, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::LinuxError, fs::Openat};
+    use core::fmt::Debug;
+
+    fn roundtrip<T: FixedField + PartialEq + Debug>(value: T) -> [u8; 64] {
+        let mut buf = [0xa5; 64];
+        let rest = __write_field(&value, &mut buf);
+        assert_eq!(rest.len(), 64 - T::SIZE);
+        assert_eq!(__read_field::<T>(&buf), Some((value, &buf[T::SIZE..])));
+        buf
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!(roundtrip(0x1234u16)[..2], [0x34, 0x12]);
+        assert_eq!(
+            roundtrip(-2isize)[..8],
+            [0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]
+        );
+        assert_eq!(roundtrip(usize::MAX - 1)[..8], (u64::MAX - 1).to_le_bytes());
+        assert_eq!(roundtrip(true)[0], 1);
+        roundtrip(ProtFlags::READ | ProtFlags::EXECUTE);
+        roundtrip(MmapFlags::MAP_PRIVATE | MmapFlags::MAP_ANONYMOUS);
+        roundtrip(TaskId(7));
+        assert_eq!(bool::read_fixed(&[2]), None);
+    }
+
+    #[test]
+    fn enums() {
+        for kind in [InvariantKind::All, InvariantKind::Custom(9)] {
+            roundtrip(kind);
+        }
+        let fault = FaultSpec::fail_syscall(56, LinuxError::ENOENT, 3);
+        let buf = roundtrip(fault);
+        assert_eq!(buf[..4], 1u32.to_le_bytes());
+        assert_eq!(buf[4..8], 3u32.to_le_bytes());
+        assert_eq!(buf[8..16], 56u64.to_le_bytes());
+        assert_eq!(buf[16..24], 2i64.to_le_bytes());
+        roundtrip(FaultSpec::Clear);
+        assert_eq!(FaultSpec::read_fixed(&[5; FaultSpec::SIZE]), None);
+    }
+
+    #[test]
+    fn paths() {
+        let mut buf = [0xa5; MAX_PATH_LEN];
+        Path::try_from("/a").unwrap().write_fixed(&mut buf);
+        assert_eq!(buf[..3], *b"/a\0");
+        assert!(buf[3..].iter().all(|&b| b == 0));
+        let full = [b'p'; MAX_PATH_LEN];
+        let path = Path::read_fixed(&full).unwrap();
+        assert_eq!(path.len(), MAX_PATH_LEN);
+        assert_eq!(Path::read_fixed(&[0xff; MAX_PATH_LEN]), None);
+    }
+
+    #[test]
+    fn record_size() {
+        assert_eq!(Openat::FIXED_SIZE, 8 + MAX_PATH_LEN + 4 + 4);
+        const { assert!(RECORD_SIZE >= ID_SIZE + Openat::FIXED_SIZE) };
+    }
+
+    #[cfg(feature = "checker")]
+    #[test]
+    fn command_layout() {
+        use crate::fs::{FileMode, AT_FDCWD};
+
+        let cmd = Openat::new(
+            AT_FDCWD,
+            Path::try_from("f").unwrap(),
+            OpenFlags::CREAT,
+            FileMode::USER_READ,
+        );
+        let record = cmd.to_fixed();
+        assert_eq!(record.len(), RECORD_SIZE);
+        assert_eq!(record[..4], (Openat::ID as u32).to_le_bytes());
+        assert_eq!(record[4..12], (AT_FDCWD as i64).to_le_bytes());
+        assert_eq!(record[12..14], *b"f\0");
+        let flags = 12 + MAX_PATH_LEN;
+        assert_eq!(
+            record[flags..flags + 4],
+            OpenFlags::CREAT.bits().to_le_bytes()
+        );
+        assert_eq!(record[flags + 4..flags + 8], 0o400u32.to_le_bytes());
+        assert!(record[flags + 8..].iter().all(|&b| b == 0));
+    }
+
+    #[cfg(feature = "harness")]
+    #[test]
+    fn command_decode() {
+        use crate::fs::{Close, AT_FDCWD};
+
+        let mut data = [0; 2 * RECORD_SIZE];
+        let fields = __write_id((&mut data[..RECORD_SIZE]).try_into().unwrap(), Openat::ID);
+        let fields = __write_field(&AT_FDCWD, fields);
+        let fields = __write_field(&Path::try_from("/x").unwrap(), fields);
+        __write_field(&OpenFlags::RDWR, fields);
+        let (cmd, rest) = Openat::from_fixed(&data).unwrap();
+        assert_eq!((cmd.dirfd, cmd.path.as_str()), (AT_FDCWD, "/x"));
+        assert_eq!(cmd.flags, OpenFlags::RDWR);
+        assert_eq!(rest.len(), RECORD_SIZE);
+        assert!(Close::from_fixed(&data).is_none());
+        assert!(Openat::from_fixed(&data[..RECORD_SIZE - 1]).is_none());
+    }
+}
@@ -1,40 +1,40 @@
-use crate::command;
+use crate::{
+    bytes::{CapacityError, FixedString},
+    command, response,
+    size::MaxSize,
+    validate::{Validate, ValidationError},
+};
+#[cfg(feature = "checker")]
+use alloc::{collections::BTreeMap, vec::Vec};
 use bitflags::bitflags;
 use core::{
-    mem::size_of,
+    fmt,
+    mem::{align_of, size_of},
     ops::{Deref, DerefMut},
-    str::{self, FromStr},
+    str,
 };
-use heapless::String;
-use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 /// Max file path length.
 pub const MAX_PATH_LEN: usize = 256;
 
-/// Path type - a fixed capacity string.
-#[derive(Debug, Clone)]
-pub struct Path(pub String<MAX_PATH_LEN>);
+/// Special `dirfd` value: resolve relative paths against the current
+/// working directory.
+pub const AT_FDCWD: isize = -100;
 
-impl Serialize for Path {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        serializer.serialize_str(&self.0)
-    }
-}
+/// Max file name length, excluding the NUL terminator.
+pub const MAX_NAME_LEN: usize = 255;
 
-impl<'de> Deserialize<'de> for Path {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        <&str>::deserialize(deserializer).map(|s| Path(String::from_str(s).unwrap()))
-    }
-}
+/// Max number of entries in a [`GetdentsResult`].
+pub const MAX_DIRENTS: usize = 32;
+
+/// Path type - a fixed capacity string.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Path(pub FixedString<MAX_PATH_LEN>);
 
 impl Deref for Path {
-    type Target = String<MAX_PATH_LEN>;
+    type Target = heapless::String<MAX_PATH_LEN>;
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -47,6 +47,18 @@ impl DerefMut for Path {
     }
 }
 
+impl TryFrom<&str> for Path {
+    type Error = CapacityError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        FixedString::try_from(s).map(Path)
+    }
+}
+
+impl MaxSize for Path {
+    const MAX_SIZE: usize = FixedString::<MAX_PATH_LEN>::MAX_SIZE;
+}
+
 impl Path {
     /// Check if the path is absolute.
     pub fn absolute(&self) -> bool {
@@ -69,10 +81,13 @@ command!(
         path: Path,
         /// The flags to use when opening the file.
         flags: OpenFlags,
-        /// The mode to use when creating the file.
+        /// The mode to use when creating the file, only needed with `O_CREAT`
+        /// or `O_TMPFILE`.
+        #[km(default)]
         mode: FileMode,
     },
-    56
+    56,
+    FdResult
 );
 
 command!(
@@ -94,7 +109,8 @@ command!(
         /// The file descriptor to get the status of.
         fd: isize,
     },
-    80
+    80,
+    FstatResult
 );
 
 command!(
@@ -107,7 +123,8 @@ command!(
         /// The length of the buffer in bytes.
         len: usize,
     },
-    61
+    61,
+    GetdentsResult
 );
 
 command!(
@@ -117,7 +134,8 @@ command!(
         /// The file descriptor to get directory entries from.
         fd: isize,
     },
-    10061
+    10061,
+    GetdentsResult
 );
 
 command!(
@@ -173,7 +191,8 @@ command!(
     ///
     /// Ref: https://man7.org/linux/man-pages/man2/getcwd.2.html
     struct Getcwd {},
-    17
+    17,
+    GetcwdResult
 );
 
 command!(
@@ -184,7 +203,8 @@ command!(
         /// The file descriptor to be duplicated.
         oldfd: isize,
     },
-    23
+    23,
+    FdResult
 );
 
 command!(
@@ -199,9 +219,214 @@ command!(
     49
 );
 
+/// Check that `fd` is not negative.
+fn check_fd(fd: isize) -> Result<(), ValidationError> {
+    if fd < 0 {
+        return Err(ValidationError::BadFd);
+    }
+    Ok(())
+}
+
+/// Check that `path` is not empty, and that `dirfd` is valid if `path` is
+/// relative.
+fn check_path_at(dirfd: isize, path: &Path) -> Result<(), ValidationError> {
+    if path.is_empty() {
+        return Err(ValidationError::EmptyPath);
+    }
+    if path.relative() && dirfd != AT_FDCWD {
+        check_fd(dirfd)?;
+    }
+    Ok(())
+}
+
+impl Validate for Openat {
+    fn validate(&self) -> Result<(), ValidationError> {
+        check_path_at(self.dirfd, &self.path)?;
+        if self.flags.contains(OpenFlags::CREAT) && self.mode.is_empty() {
+            return Err(ValidationError::MissingMode);
+        }
+        Ok(())
+    }
+}
+
+impl Validate for Close {
+    fn validate(&self) -> Result<(), ValidationError> {
+        check_fd(self.fd)
+    }
+}
+
+impl Validate for Fstat {
+    fn validate(&self) -> Result<(), ValidationError> {
+        check_fd(self.fd)
+    }
+}
+
+impl Validate for Getdents {
+    fn validate(&self) -> Result<(), ValidationError> {
+        check_fd(self.fd)?;
+        if self.len == 0 {
+            return Err(ValidationError::ZeroLength);
+        }
+        Ok(())
+    }
+}
+
+impl Validate for Getdents1 {
+    fn validate(&self) -> Result<(), ValidationError> {
+        check_fd(self.fd)
+    }
+}
+
+impl Validate for Linkat {
+    fn validate(&self) -> Result<(), ValidationError> {
+        check_path_at(self.olddirfd, &self.oldpath)?;
+        check_path_at(self.newdirfd, &self.newpath)
+    }
+}
+
+impl Validate for Unlinkat {
+    fn validate(&self) -> Result<(), ValidationError> {
+        check_path_at(self.dirfd, &self.path)?;
+        if !UnlinkatFlags::all().contains(self.flags) {
+            return Err(ValidationError::ConflictingFlags);
+        }
+        Ok(())
+    }
+}
+
+impl Validate for Mkdirat {
+    fn validate(&self) -> Result<(), ValidationError> {
+        check_path_at(self.dirfd, &self.path)
+    }
+}
+
+impl Validate for Getcwd {}
+
+impl Validate for Dup {
+    fn validate(&self) -> Result<(), ValidationError> {
+        check_fd(self.oldfd)
+    }
+}
+
+impl Validate for Chdir {
+    fn validate(&self) -> Result<(), ValidationError> {
+        if self.path.is_empty() {
+            return Err(ValidationError::EmptyPath);
+        }
+        Ok(())
+    }
+}
+
+/// Builder of [`Openat`] commands. By default the file is opened read-only
+/// relative to the current working directory.
+#[derive(Debug, Clone)]
+pub struct OpenatBuilder {
+    cmd: Openat,
+}
+
+impl Openat {
+    /// Start building a command opening `path`.
+    pub fn builder(path: Path) -> OpenatBuilder {
+        OpenatBuilder {
+            cmd: Openat::new(AT_FDCWD, path, OpenFlags::RDONLY, FileMode::empty()),
+        }
+    }
+}
+
+impl OpenatBuilder {
+    /// Resolve a relative path against the directory `dirfd`.
+    pub fn dirfd(mut self, dirfd: isize) -> Self {
+        self.cmd.dirfd = dirfd;
+        self
+    }
+
+    /// Add `flags` to the open flags.
+    pub fn flags(mut self, flags: OpenFlags) -> Self {
+        self.cmd.flags |= flags;
+        self
+    }
+
+    /// Open for writing only.
+    pub fn write_only(mut self) -> Self {
+        self.cmd.flags.remove(OpenFlags::RDWR);
+        self.cmd.flags.insert(OpenFlags::WRONLY);
+        self
+    }
+
+    /// Open for reading and writing.
+    pub fn read_write(mut self) -> Self {
+        self.cmd.flags.remove(OpenFlags::WRONLY);
+        self.cmd.flags.insert(OpenFlags::RDWR);
+        self
+    }
+
+    /// Create the file with `mode` if it does not exist.
+    pub fn create(mut self, mode: FileMode) -> Self {
+        self.cmd.flags.insert(OpenFlags::CREAT);
+        self.cmd.mode = mode;
+        self
+    }
+
+    /// Truncate the file to zero length.
+    pub fn truncate(mut self) -> Self {
+        self.cmd.flags.insert(OpenFlags::TRUNC);
+        self
+    }
+
+    /// Append to the end of the file on each write.
+    pub fn append(mut self) -> Self {
+        self.cmd.flags.insert(OpenFlags::APPEND);
+        self
+    }
+
+    /// Fail if the path is not a directory.
+    pub fn directory(mut self) -> Self {
+        self.cmd.flags.insert(OpenFlags::DIRECTORY);
+        self
+    }
+
+    /// Build the command.
+    pub fn build(self) -> Openat {
+        self.cmd
+    }
+}
+
+response!(
+    /// Result of commands returning a new file descriptor, i.e.
+    /// [`Openat`] and [`Dup`].
+    struct FdResult {
+        /// The new file descriptor.
+        fd: isize,
+    }
+);
+
+response!(
+    /// Result of [`Getcwd`].
+    struct GetcwdResult {
+        /// Absolute path of the current working directory.
+        path: Path,
+    }
+);
+
+response!(
+    /// Result of [`Fstat`].
+    struct FstatResult {
+        /// The file status.
+        stat: LibcStat,
+    }
+);
+
+response!(
+    /// Result of [`Getdents`] and [`Getdents1`].
+    struct GetdentsResult {
+        /// Directory entries in the order returned by the kernel.
+        entries: heapless::Vec<Dirent, MAX_DIRENTS>,
+    }
+);
+
 bitflags! {
     /// Flags for the `Open` command.
-    #[derive(Debug, Clone, Copy, Default)]
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
     pub struct OpenFlags: u32 {
         /// Open for reading only.
         const RDONLY = 0o00000000;
@@ -220,6 +445,10 @@ bitflags! {
     }
 }
 
+impl MaxSize for OpenFlags {
+    const MAX_SIZE: usize = u32::MAX_SIZE;
+}
+
 impl Serialize for OpenFlags {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -242,7 +471,7 @@ impl<'de> Deserialize<'de> for OpenFlags {
 
 bitflags! {
     /// File mode.
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
     pub struct FileMode: u32 {
         /// User readable.
         const USER_READ = 0o400;
@@ -265,6 +494,10 @@ bitflags! {
     }
 }
 
+impl MaxSize for FileMode {
+    const MAX_SIZE: usize = u32::MAX_SIZE;
+}
+
 impl Serialize for FileMode {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -287,13 +520,17 @@ impl<'de> Deserialize<'de> for FileMode {
 
 bitflags! {
     /// Unlink flags.
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub struct UnlinkatFlags: u32 {
         /// Directory is to be deleted.
         const REMOVEDIR = 0x200;
     }
 }
 
+impl MaxSize for UnlinkatFlags {
+    const MAX_SIZE: usize = u32::MAX_SIZE;
+}
+
 impl Serialize for UnlinkatFlags {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -315,7 +552,7 @@ impl<'de> Deserialize<'de> for UnlinkatFlags {
 }
 
 /// File kind.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum FileKind {
     Unknown = 0,
@@ -328,8 +565,85 @@ pub enum FileKind {
     Sockect = 12,
 }
 
+impl FileKind {
+    /// Bit mask for the file type bit field of `st_mode`.
+    pub const S_IFMT: u32 = 0o170000;
+
+    /// Get the file kind from `st_mode`, ignoring the permission bits.
+    pub fn from_mode(mode: u32) -> Self {
+        // `S_IFMT` bits are the `d_type` value shifted left by 12.
+        Self::try_from(((mode & Self::S_IFMT) >> 12) as u8).unwrap_or(Self::Unknown)
+    }
+
+    /// The `S_IFMT` bits of `st_mode` for this file kind.
+    pub fn mode_bits(self) -> u32 {
+        (self as u32) << 12
+    }
+}
+
+impl From<FileKind> for u8 {
+    fn from(kind: FileKind) -> Self {
+        kind as u8
+    }
+}
+
+impl TryFrom<u8> for FileKind {
+    type Error = u8;
+
+    /// Convert a `d_type` value, returning it back if it is not a valid kind.
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(FileKind::Unknown),
+            1 => Ok(FileKind::Fifo),
+            2 => Ok(FileKind::CharDevice),
+            4 => Ok(FileKind::Directory),
+            6 => Ok(FileKind::BlockDevice),
+            8 => Ok(FileKind::File),
+            10 => Ok(FileKind::Symlink),
+            12 => Ok(FileKind::Sockect),
+            _ => Err(value),
+        }
+    }
+}
+
+impl fmt::Display for FileKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FileKind::Unknown => "unknown",
+            FileKind::Fifo => "fifo",
+            FileKind::CharDevice => "character special file",
+            FileKind::Directory => "directory",
+            FileKind::BlockDevice => "block special file",
+            FileKind::File => "regular file",
+            FileKind::Symlink => "symbolic link",
+            FileKind::Sockect => "socket",
+        })
+    }
+}
+
+impl Serialize for FileKind {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u8(*self as u8)
+    }
+}
+
+impl<'de> Deserialize<'de> for FileKind {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = u8::deserialize(deserializer)?;
+        FileKind::try_from(value).map_err(|_| {
+            de::Error::invalid_value(de::Unexpected::Unsigned(value as u64), &"a file kind")
+        })
+    }
+}
+
 /// libc directory entry defination.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 #[repr(C)]
 pub struct LibcDirent {
     /// 64-bit inode number
@@ -351,34 +665,324 @@ impl LibcDirent {
     /// The maximum size of a dirent.
     pub const MAX_SIZE: usize = Self::MIN_SIZE + 256;
 
+    /// The maximum record length of a dirent, i.e. of one with a name of
+    /// [`MAX_NAME_LEN`] bytes, NUL terminator and alignment padding included.
+    pub const MAX_RECLEN: usize = DirentWriter::reclen(MAX_NAME_LEN);
+
     /// Maximum buffer size which can only contain one dirent.
     pub const ONE_DIRENT_BUF_SIZE: usize = Self::MIN_SIZE * 2;
 
     /// Get the file kind of the directory entry.
     pub fn kind(&self) -> FileKind {
-        match self.type_ {
-            1 => FileKind::Fifo,
-            2 => FileKind::CharDevice,
-            4 => FileKind::Directory,
-            6 => FileKind::BlockDevice,
-            8 => FileKind::File,
-            10 => FileKind::Symlink,
-            12 => FileKind::Sockect,
-            _ => FileKind::Unknown,
-        }
+        FileKind::try_from(self.type_).unwrap_or(FileKind::Unknown)
     }
 
-    /// Get the name of the directory entry.
+    /// Get the name of the directory entry, or an empty name if the entry
+    /// is malformed. See [`LibcDirent::try_name`].
     pub fn name(&self) -> &str {
-        unsafe {
-            str::from_utf8_unchecked(&self.name[..self.reclen as usize - Self::MIN_SIZE])
-                .trim_end_matches('\0')
+        self.try_name().unwrap_or("")
+    }
+
+    /// Get the name of the directory entry, checking `reclen` and that the
+    /// name is NUL-terminated valid UTF-8.
+    pub fn try_name(&self) -> Result<&str, DirentError> {
+        let reclen = self.reclen as usize;
+        if reclen <= Self::MIN_SIZE || reclen > Self::MAX_RECLEN {
+            return Err(DirentError::BadReclen(self.reclen));
         }
+        // The record may end in alignment padding past the name array.
+        let area = (reclen - Self::MIN_SIZE).min(self.name.len());
+        parse_name(&self.name[..area])
     }
 }
 
-/// libc file stat defination.
+/// Error in a directory entry record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DirentError {
+    /// `reclen` is too small for a name, too large for a dirent, or exceeds
+    /// the buffer.
+    BadReclen(u16),
+    /// The buffer ends inside the fixed part of a record.
+    Truncated,
+    /// The name is not NUL-terminated within the record.
+    Unterminated,
+    /// The name is not valid UTF-8.
+    InvalidUtf8,
+}
+
+impl fmt::Display for DirentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadReclen(reclen) => write!(f, "bad dirent reclen {}", reclen),
+            Self::Truncated => f.write_str("truncated dirent"),
+            Self::Unterminated => f.write_str("dirent name is not NUL-terminated"),
+            Self::InvalidUtf8 => f.write_str("dirent name is not valid UTF-8"),
+        }
+    }
+}
+
+/// Parse the NUL-terminated name in the name area of a record.
+fn parse_name(area: &[u8]) -> Result<&str, DirentError> {
+    let len = area
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or(DirentError::Unterminated)?;
+    str::from_utf8(&area[..len]).map_err(|_| DirentError::InvalidUtf8)
+}
+
+/// Reader of [`LibcDirent`] records in a byte buffer, as written by the
+/// kernel or by [`DirentWriter`], yielding checked [`Dirent`]s.
+///
+/// Unlike casting the buffer to `LibcDirent`, reading never goes past the
+/// buffer. The reader stops after the first malformed record.
 #[derive(Debug, Clone)]
+pub struct DirentReader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> DirentReader<'a> {
+    /// Create a reader over the records in `buf`.
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    fn read(&mut self) -> Result<Dirent, DirentError> {
+        let head = self
+            .buf
+            .get(..LibcDirent::MIN_SIZE)
+            .ok_or(DirentError::Truncated)?;
+        let (ino, rest) = head.split_at(size_of::<usize>());
+        let (_off, rest) = rest.split_at(size_of::<usize>());
+        let (reclen, type_) = rest.split_at(size_of::<u16>());
+        let reclen = u16::from_ne_bytes(reclen.try_into().unwrap());
+        let len = reclen as usize;
+        if len <= LibcDirent::MIN_SIZE || len > LibcDirent::MAX_RECLEN || len > self.buf.len() {
+            return Err(DirentError::BadReclen(reclen));
+        }
+        let name = parse_name(&self.buf[LibcDirent::MIN_SIZE..len])?;
+        self.buf = &self.buf[len..];
+        Ok(Dirent {
+            ino: usize::from_ne_bytes(ino.try_into().unwrap()) as u64,
+            kind: FileKind::try_from(type_[0]).unwrap_or(FileKind::Unknown),
+            name: FixedString::try_from(name).map_err(|_| DirentError::BadReclen(reclen))?,
+        })
+    }
+}
+
+impl Iterator for DirentReader<'_> {
+    type Item = Result<Dirent, DirentError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.is_empty() {
+            return None;
+        }
+        let dirent = self.read();
+        if dirent.is_err() {
+            self.buf = &[];
+        }
+        Some(dirent)
+    }
+}
+
+/// Directory entry that can be sent over the wire, the compact counterpart
+/// of [`LibcDirent`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Dirent {
+    /// Inode number.
+    pub ino: u64,
+    /// File kind.
+    pub kind: FileKind,
+    /// File name.
+    pub name: FixedString<MAX_NAME_LEN>,
+}
+
+impl TryFrom<&LibcDirent> for Dirent {
+    type Error = DirentError;
+
+    fn try_from(dirent: &LibcDirent) -> Result<Self, Self::Error> {
+        let name = dirent.try_name()?;
+        Ok(Self {
+            ino: dirent.ino as u64,
+            kind: dirent.kind(),
+            name: FixedString::try_from(name).map_err(|_| DirentError::BadReclen(dirent.reclen))?,
+        })
+    }
+}
+
+impl From<&Dirent> for LibcDirent {
+    /// Convert to the libc layout, with `off` set to zero.
+    fn from(dirent: &Dirent) -> Self {
+        let mut name = [0; 256];
+        name[..dirent.name.len()].copy_from_slice(dirent.name.as_bytes());
+        Self {
+            ino: dirent.ino as usize,
+            off: 0,
+            reclen: DirentWriter::reclen(dirent.name.len()) as u16,
+            type_: dirent.kind.into(),
+            name,
+        }
+    }
+}
+
+impl Dirent {
+    /// Check if the entry is `.` or `..`.
+    pub fn is_dot(&self) -> bool {
+        matches!(self.name.as_str(), "." | "..")
+    }
+}
+
+/// Differences between an expected and an actual directory listing.
+#[cfg(feature = "checker")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirentDiff<'a> {
+    /// Expected entries not listed.
+    pub missing: Vec<&'a Dirent>,
+    /// Listed entries not expected, including repeated names.
+    pub extra: Vec<&'a Dirent>,
+    /// Entries listed with another kind, as `(expected, actual)`.
+    pub kind_mismatch: Vec<(&'a Dirent, &'a Dirent)>,
+    /// Entries listed with another inode number, as `(expected, actual)`.
+    /// Only checked if inode numbers are not ignored.
+    pub ino_mismatch: Vec<(&'a Dirent, &'a Dirent)>,
+    /// The same entries are listed in another order. Only checked by
+    /// order-sensitive comparisons, see
+    /// [`ComparePolicy`](crate::compare::ComparePolicy).
+    pub reordered: bool,
+}
+
+#[cfg(feature = "checker")]
+impl DirentDiff<'_> {
+    /// Check if the listings agree.
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty()
+            && self.extra.is_empty()
+            && self.kind_mismatch.is_empty()
+            && self.ino_mismatch.is_empty()
+            && !self.reordered
+    }
+}
+
+/// Compare the directory listing `actual` against `expected` by name,
+/// ignoring order, and inode numbers if `ignore_inodes` is set, and `.` and
+/// `..` if `skip_dots` is set.
+///
+/// An entry of kind [`FileKind::Unknown`] matches any kind, since some file
+/// systems do not report kinds in directory entries.
+#[cfg(feature = "checker")]
+pub fn diff_dirents<'a>(
+    expected: &'a [Dirent],
+    actual: &'a [Dirent],
+    skip_dots: bool,
+    ignore_inodes: bool,
+) -> DirentDiff<'a> {
+    let keep = |entry: &&Dirent| !(skip_dots && entry.is_dot());
+    let mut pending: BTreeMap<&str, &Dirent> = expected
+        .iter()
+        .filter(keep)
+        .map(|entry| (entry.name.as_str(), entry))
+        .collect();
+    let mut diff = DirentDiff::default();
+    for entry in actual.iter().filter(keep) {
+        match pending.remove(entry.name.as_str()) {
+            Some(exp) => {
+                let unknown = exp.kind == FileKind::Unknown || entry.kind == FileKind::Unknown;
+                if !unknown && exp.kind != entry.kind {
+                    diff.kind_mismatch.push((exp, entry));
+                }
+                if !ignore_inodes && exp.ino != entry.ino {
+                    diff.ino_mismatch.push((exp, entry));
+                }
+            }
+            None => diff.extra.push(entry),
+        }
+    }
+    diff.missing = pending.into_values().collect();
+    diff
+}
+
+#[cfg(feature = "checker")]
+impl GetdentsResult {
+    /// Compare the entries against `expected` ignoring inode numbers, see
+    /// [`diff_dirents`].
+    pub fn diff<'a>(&'a self, expected: &'a [Dirent], skip_dots: bool) -> DirentDiff<'a> {
+        diff_dirents(expected, &self.entries, skip_dots, true)
+    }
+}
+
+/// Writer that appends [`LibcDirent`] records into a caller-provided buffer.
+///
+/// Records are laid out like `getdents64` does: the name is NUL-terminated and
+/// `reclen` is rounded up so that every record starts at an aligned offset.
+pub struct DirentWriter<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+    count: usize,
+}
+
+impl<'a> DirentWriter<'a> {
+    /// Create a writer over an empty buffer.
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self {
+            buf,
+            pos: 0,
+            count: 0,
+        }
+    }
+
+    /// Record length of a dirent whose name is `name_len` bytes long.
+    pub const fn reclen(name_len: usize) -> usize {
+        let align = align_of::<LibcDirent>();
+        (LibcDirent::MIN_SIZE + name_len + 1 + align - 1) & !(align - 1)
+    }
+
+    /// Append a directory entry.
+    ///
+    /// Return `false` and leave the buffer untouched if the entry does not fit
+    /// in the remaining space, or if the name is too long for a dirent.
+    pub fn push(&mut self, ino: usize, kind: FileKind, name: &str) -> bool {
+        let reclen = Self::reclen(name.len());
+        if name.len() > MAX_NAME_LEN || self.pos + reclen > self.buf.len() {
+            return false;
+        }
+        let rec = &mut self.buf[self.pos..self.pos + reclen];
+        let (ino_bytes, rest) = rec.split_at_mut(size_of::<usize>());
+        let (off_bytes, rest) = rest.split_at_mut(size_of::<usize>());
+        let (reclen_bytes, rest) = rest.split_at_mut(size_of::<u16>());
+        let (type_bytes, name_bytes) = rest.split_at_mut(size_of::<u8>());
+        ino_bytes.copy_from_slice(&ino.to_ne_bytes());
+        off_bytes.copy_from_slice(&(self.pos + reclen).to_ne_bytes());
+        reclen_bytes.copy_from_slice(&(reclen as u16).to_ne_bytes());
+        type_bytes[0] = kind as u8;
+        name_bytes[..name.len()].copy_from_slice(name.as_bytes());
+        name_bytes[name.len()..].fill(0);
+        self.pos += reclen;
+        self.count += 1;
+        true
+    }
+
+    /// Number of bytes written so far.
+    pub fn len(&self) -> usize {
+        self.pos
+    }
+
+    /// Check if no entry has been written.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Number of entries written so far.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// The written part of the buffer.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.pos]
+    }
+}
+
+/// libc file stat defination.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[repr(C)]
 pub struct LibcStat {
     pub dev: u64,
@@ -388,9 +992,11 @@ pub struct LibcStat {
     pub uid: u32,
     pub gid: u32,
     pub rdev: u64,
+    #[serde(skip)]
     _pad1: u64,
     pub size: u64,
     pub blksize: u32,
+    #[serde(skip)]
     _pad2: u32,
     pub blocks: u64,
     pub atime_sec: u64,
@@ -399,6 +1005,7 @@ pub struct LibcStat {
     pub mtime_nsec: u64,
     pub ctime_sec: u64,
     pub ctime_nsec: u64,
+    #[serde(skip)]
     _pad3: u64,
 }
 
@@ -410,16 +1017,348 @@ impl LibcStat {
 
     /// Kind of the file.
     pub fn kind(&self) -> FileKind {
-        let kind_bits = self.mode & 0o170000;
-        match kind_bits {
-            0o010000 => FileKind::Fifo,
-            0o020000 => FileKind::CharDevice,
-            0o040000 => FileKind::Directory,
-            0o060000 => FileKind::BlockDevice,
-            0o100000 => FileKind::File,
-            0o120000 => FileKind::Symlink,
-            0o140000 => FileKind::Sockect,
-            _ => FileKind::Unknown,
+        FileKind::from_mode(self.mode)
+    }
+
+    /// Copy of the stat with the `ignored` fields zeroed, so that stats from
+    /// different systems can be compared.
+    pub fn normalized(&self, ignored: StatFields) -> Self {
+        let mut stat = self.clone();
+        for field in ignored.iter() {
+            match field {
+                StatFields::DEV => stat.dev = 0,
+                StatFields::INO => stat.ino = 0,
+                StatFields::MODE => stat.mode = 0,
+                StatFields::NLINK => stat.nlink = 0,
+                StatFields::UID => stat.uid = 0,
+                StatFields::GID => stat.gid = 0,
+                StatFields::RDEV => stat.rdev = 0,
+                StatFields::SIZE => stat.size = 0,
+                StatFields::BLKSIZE => stat.blksize = 0,
+                StatFields::BLOCKS => stat.blocks = 0,
+                StatFields::ATIME => (stat.atime_sec, stat.atime_nsec) = (0, 0),
+                StatFields::MTIME => (stat.mtime_sec, stat.mtime_nsec) = (0, 0),
+                StatFields::CTIME => (stat.ctime_sec, stat.ctime_nsec) = (0, 0),
+                _ => {}
+            }
+        }
+        stat
+    }
+}
+
+bitflags! {
+    /// Set of [`LibcStat`] fields, used to select fields to ignore and to
+    /// report differing fields.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct StatFields: u16 {
+        /// Device containing the file.
+        const DEV = 1 << 0;
+        /// Inode number.
+        const INO = 1 << 1;
+        /// File type and permission bits.
+        const MODE = 1 << 2;
+        /// Number of hard links.
+        const NLINK = 1 << 3;
+        /// Owner user id.
+        const UID = 1 << 4;
+        /// Owner group id.
+        const GID = 1 << 5;
+        /// Device id of a special file.
+        const RDEV = 1 << 6;
+        /// Size in bytes.
+        const SIZE = 1 << 7;
+        /// Preferred I/O block size.
+        const BLKSIZE = 1 << 8;
+        /// Number of allocated 512-byte blocks.
+        const BLOCKS = 1 << 9;
+        /// Last access time.
+        const ATIME = 1 << 10;
+        /// Last modification time.
+        const MTIME = 1 << 11;
+        /// Last status change time.
+        const CTIME = 1 << 12;
+
+        /// All timestamps.
+        const TIMES = Self::ATIME.bits() | Self::MTIME.bits() | Self::CTIME.bits();
+        /// Fields that depend on the system rather than on the operations
+        /// performed: device and inode numbers, block layout and timestamps.
+        const SYSTEM_DEPENDENT = Self::DEV.bits()
+            | Self::INO.bits()
+            | Self::RDEV.bits()
+            | Self::BLKSIZE.bits()
+            | Self::BLOCKS.bits()
+            | Self::TIMES.bits();
+    }
+}
+
+/// Fields in which `a` and `b` differ.
+pub fn stat_diff(a: &LibcStat, b: &LibcStat) -> StatFields {
+    let mut diff = StatFields::empty();
+    diff.set(StatFields::DEV, a.dev != b.dev);
+    diff.set(StatFields::INO, a.ino != b.ino);
+    diff.set(StatFields::MODE, a.mode != b.mode);
+    diff.set(StatFields::NLINK, a.nlink != b.nlink);
+    diff.set(StatFields::UID, a.uid != b.uid);
+    diff.set(StatFields::GID, a.gid != b.gid);
+    diff.set(StatFields::RDEV, a.rdev != b.rdev);
+    diff.set(StatFields::SIZE, a.size != b.size);
+    diff.set(StatFields::BLKSIZE, a.blksize != b.blksize);
+    diff.set(StatFields::BLOCKS, a.blocks != b.blocks);
+    diff.set(
+        StatFields::ATIME,
+        (a.atime_sec, a.atime_nsec) != (b.atime_sec, b.atime_nsec),
+    );
+    diff.set(
+        StatFields::MTIME,
+        (a.mtime_sec, a.mtime_nsec) != (b.mtime_sec, b.mtime_nsec),
+    );
+    diff.set(
+        StatFields::CTIME,
+        (a.ctime_sec, a.ctime_nsec) != (b.ctime_sec, b.ctime_nsec),
+    );
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dirent_writer_layout() {
+        let mut buf = [0xff; 256];
+        let mut writer = DirentWriter::new(&mut buf);
+        assert!(writer.is_empty());
+        assert!(writer.push(1, FileKind::Directory, "."));
+        assert!(writer.push(7, FileKind::File, "file"));
+        assert_eq!(writer.count(), 2);
+        let first = DirentWriter::reclen(1);
+        assert_eq!(writer.len(), first + DirentWriter::reclen(4));
+        assert!(first.is_multiple_of(align_of::<LibcDirent>()));
+
+        let rec = &writer.as_bytes()[first..];
+        let off = usize::from_ne_bytes(rec[8..16].try_into().unwrap());
+        let reclen = u16::from_ne_bytes(rec[16..18].try_into().unwrap());
+        assert_eq!(usize::from_ne_bytes(rec[..8].try_into().unwrap()), 7);
+        assert_eq!(off, writer.len());
+        assert_eq!(reclen as usize, DirentWriter::reclen(4));
+        assert_eq!(rec[18], FileKind::File as u8);
+        assert_eq!(&rec[19..24], b"file\0");
+        assert!(rec[24..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn dirent_writer_full() {
+        let mut buf = [0; 32];
+        let mut writer = DirentWriter::new(&mut buf);
+        assert!(writer.push(1, FileKind::File, "a"));
+        assert!(!writer.push(2, FileKind::File, "b"));
+        assert_eq!(writer.count(), 1);
+        assert_eq!(writer.len(), DirentWriter::reclen(1));
+    }
+
+    #[test]
+    fn dirent_writer_longest_name() {
+        let name = [b'n'; MAX_NAME_LEN + 1];
+        let longest = str::from_utf8(&name[..MAX_NAME_LEN]).unwrap();
+        let mut buf = [0; 2 * LibcDirent::MAX_RECLEN];
+        let mut writer = DirentWriter::new(&mut buf);
+        assert!(writer.push(1, FileKind::File, &longest[..MAX_NAME_LEN - 2]));
+        assert!(writer.push(2, FileKind::File, longest));
+        assert_eq!(writer.len(), 2 * LibcDirent::MAX_RECLEN);
+        let mut writer = DirentWriter::new(&mut buf);
+        assert!(!writer.push(3, FileKind::File, str::from_utf8(&name).unwrap()));
+        assert!(writer.is_empty());
+    }
+
+    /// Buffer holding one record written by `DirentWriter`.
+    fn record(name: &str) -> ([u8; LibcDirent::MAX_RECLEN], usize) {
+        let mut buf = [0; LibcDirent::MAX_RECLEN];
+        let mut writer = DirentWriter::new(&mut buf);
+        assert!(writer.push(5, FileKind::Symlink, name));
+        let len = writer.len();
+        (buf, len)
+    }
+
+    #[test]
+    fn dirent_reader_records() {
+        let mut buf = [0; 128];
+        let mut writer = DirentWriter::new(&mut buf);
+        writer.push(1, FileKind::Directory, ".");
+        writer.push(2, FileKind::File, "file");
+        let len = writer.len();
+        let mut reader = DirentReader::new(&buf[..len]);
+        let first = reader.next().unwrap().unwrap();
+        assert_eq!(
+            (first.ino, first.kind, first.name.as_str()),
+            (1, FileKind::Directory, ".")
+        );
+        let second = reader.next().unwrap().unwrap();
+        assert_eq!(
+            (second.ino, second.kind, second.name.as_str()),
+            (2, FileKind::File, "file")
+        );
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn dirent_reader_truncated() {
+        let (buf, len) = record("file");
+        let mut reader = DirentReader::new(&buf[..LibcDirent::MIN_SIZE - 1]);
+        assert_eq!(reader.next(), Some(Err(DirentError::Truncated)));
+        assert!(reader.next().is_none());
+        let reclen = len as u16;
+        let mut reader = DirentReader::new(&buf[..len - 1]);
+        assert_eq!(reader.next(), Some(Err(DirentError::BadReclen(reclen))));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn dirent_reader_bad_name() {
+        let (mut buf, len) = record("file");
+        buf[LibcDirent::MIN_SIZE..len].fill(b'x');
+        let mut reader = DirentReader::new(&buf[..len]);
+        assert_eq!(reader.next(), Some(Err(DirentError::Unterminated)));
+
+        let (mut buf, len) = record("file");
+        buf[LibcDirent::MIN_SIZE] = 0xff;
+        let mut reader = DirentReader::new(&buf[..len]);
+        assert_eq!(reader.next(), Some(Err(DirentError::InvalidUtf8)));
+    }
+
+    #[test]
+    fn dirent_reader_longest_name() {
+        let name = [b'n'; MAX_NAME_LEN];
+        let name = str::from_utf8(&name).unwrap();
+        let (buf, len) = record(name);
+        assert_eq!(len, LibcDirent::MAX_RECLEN);
+        let dirent = DirentReader::new(&buf[..len]).next().unwrap().unwrap();
+        assert_eq!(dirent.name.as_str(), name);
+
+        let mut libc = LibcDirent::from(&dirent);
+        assert_eq!(libc.try_name(), Ok(name));
+        libc.reclen = LibcDirent::MAX_RECLEN as u16 + 1;
+        assert_eq!(libc.try_name(), Err(DirentError::BadReclen(libc.reclen)));
+    }
+
+    #[test]
+    fn dirent_libc_roundtrip() {
+        let name = [b'n'; MAX_NAME_LEN];
+        for len in [1, MAX_NAME_LEN - 3, MAX_NAME_LEN] {
+            let dirent = Dirent {
+                ino: 42,
+                kind: FileKind::Directory,
+                name: FixedString::try_from(str::from_utf8(&name[..len]).unwrap()).unwrap(),
+            };
+            let libc = LibcDirent::from(&dirent);
+            assert_eq!(libc.reclen as usize, DirentWriter::reclen(len));
+            assert_eq!(Dirent::try_from(&libc), Ok(dirent));
+        }
+    }
+
+    fn stat() -> LibcStat {
+        LibcStat {
+            dev: 1,
+            ino: 2,
+            mode: FileKind::File.mode_bits() | 0o644,
+            nlink: 1,
+            uid: 1000,
+            gid: 1000,
+            size: 42,
+            blksize: 4096,
+            blocks: 8,
+            atime_sec: 10,
+            mtime_sec: 11,
+            ctime_sec: 12,
+            ctime_nsec: 13,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn stat_normalized() {
+        let stat = stat();
+        assert_eq!(stat.normalized(StatFields::empty()), stat);
+        let normalized = stat.normalized(StatFields::SYSTEM_DEPENDENT);
+        assert_eq!(
+            (normalized.dev, normalized.ino, normalized.blocks),
+            (0, 0, 0)
+        );
+        assert_eq!((normalized.mtime_sec, normalized.ctime_nsec), (0, 0));
+        assert_eq!((normalized.mode, normalized.size), (stat.mode, 42));
+        assert_eq!(normalized.kind(), FileKind::File);
+        assert_eq!(normalized.mode(), FileMode::from_bits_truncate(0o644));
+        assert_eq!(stat.normalized(StatFields::all()), LibcStat::default());
+    }
+
+    #[test]
+    fn stat_diff_fields() {
+        let a = stat();
+        assert_eq!(stat_diff(&a, &a), StatFields::empty());
+        let b = LibcStat {
+            ino: 3,
+            size: 0,
+            atime_nsec: 1,
+            ..a.clone()
+        };
+        let diff = stat_diff(&a, &b);
+        assert_eq!(diff, StatFields::INO | StatFields::SIZE | StatFields::ATIME);
+        let ignored = StatFields::SYSTEM_DEPENDENT;
+        assert_eq!(
+            stat_diff(&a.normalized(ignored), &b.normalized(ignored)),
+            StatFields::SIZE
+        );
+        assert_eq!(
+            stat_diff(&a, &LibcStat::default()),
+            StatFields::all() - StatFields::RDEV
+        );
+    }
+
+    #[cfg(feature = "checker")]
+    fn dirent(ino: u64, kind: FileKind, name: &str) -> Dirent {
+        Dirent {
+            ino,
+            kind,
+            name: FixedString::try_from(name).unwrap(),
         }
     }
+
+    #[cfg(feature = "checker")]
+    #[test]
+    fn dirents_diff() {
+        let expected = [
+            dirent(1, FileKind::Directory, "."),
+            dirent(1, FileKind::Directory, ".."),
+            dirent(2, FileKind::File, "a"),
+            dirent(3, FileKind::Directory, "b"),
+            dirent(4, FileKind::File, "c"),
+        ];
+        // Order and inode numbers do not matter.
+        let actual = [
+            dirent(9, FileKind::File, "c"),
+            dirent(8, FileKind::File, "a"),
+            dirent(1, FileKind::Directory, ".."),
+            dirent(1, FileKind::Directory, "."),
+            dirent(7, FileKind::Directory, "b"),
+        ];
+        assert!(diff_dirents(&expected, &actual, false, true).is_empty());
+        let diff = diff_dirents(&expected, &actual, false, false);
+        assert_eq!(diff.ino_mismatch.len(), 3);
+        assert_eq!(diff.ino_mismatch[0], (&expected[4], &actual[0]));
+
+        let actual = [
+            dirent(2, FileKind::Symlink, "a"),
+            dirent(3, FileKind::Unknown, "b"),
+            dirent(5, FileKind::File, "d"),
+            dirent(5, FileKind::File, "d"),
+        ];
+        let diff = diff_dirents(&expected, &actual, true, true);
+        assert_eq!(diff.missing, [&expected[4]]);
+        assert_eq!(diff.extra, [&actual[2], &actual[3]]);
+        assert_eq!(diff.kind_mismatch, [(&expected[2], &actual[0])]);
+        assert!(!diff.reordered);
+        assert!(!diff.is_empty());
+
+        let diff = diff_dirents(&expected, &actual, false, true);
+        assert_eq!(diff.missing, [&expected[0], &expected[1], &expected[4]]);
+    }
 }
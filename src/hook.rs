@@ -0,0 +1,155 @@
+//! On the harness, hooks are passed to the `from_bytes_hooked` method of
+//! commands and the `to_slice_hooked` method of responses, and dispatched
+//! statically. On the checker, a single global hook is registered with
+//! [`set_hook`] and called when encoding commands and responses and when
+//! decoding responses.
+
+/// Callbacks on command encoding and decoding. All methods do nothing by
+/// default.
+pub trait Hook {
+    /// A command with id `id` was encoded into `len` bytes.
+    fn on_encode(&self, _id: usize, _len: usize) {}
+
+    /// A command with id `id` was decoded from `len` bytes.
+    fn on_decode(&self, _id: usize, _len: usize) {}
+
+    /// A response of type `name` was encoded into `len` bytes.
+    fn on_encode_response(&self, _name: &'static str, _len: usize) {}
+
+    /// A response of type `name` was decoded from `len` bytes.
+    fn on_decode_response(&self, _name: &'static str, _len: usize) {}
+}
+
+#[cfg(feature = "checker")]
+pub use self::global::*;
+
+#[cfg(feature = "checker")]
+mod global {
+    use super::Hook;
+    use alloc::boxed::Box;
+    use core::{
+        ptr,
+        sync::atomic::{AtomicPtr, Ordering},
+    };
+
+    type HookRef = &'static (dyn Hook + Sync);
+
+    static HOOK: AtomicPtr<HookRef> = AtomicPtr::new(ptr::null_mut());
+
+    /// Register the global hook, replacing the previous one.
+    ///
+    /// Each call leaks a pointer-sized allocation, so hooks should be set
+    /// once at startup rather than swapped frequently.
+    pub fn set_hook(hook: HookRef) {
+        HOOK.store(Box::leak(Box::new(hook)), Ordering::Release);
+    }
+
+    /// The registered global hook, if any.
+    pub fn hook() -> Option<HookRef> {
+        let hook = HOOK.load(Ordering::Acquire);
+        // SAFETY: `HOOK` is either null or set by `set_hook` to a leaked
+        // allocation that is never freed or mutated, so it is valid for reads
+        // for the rest of the program.
+        unsafe { hook.as_ref().copied() }
+    }
+
+    /// Report an encoded command to the global hook. Used by [`command!`](crate::command).
+    #[doc(hidden)]
+    pub fn encoded(id: usize, len: usize) {
+        if let Some(hook) = hook() {
+            hook.on_encode(id, len);
+        }
+    }
+
+    /// Report an encoded response to the global hook. Used by [`response!`](crate::response).
+    #[doc(hidden)]
+    pub fn encoded_response(name: &'static str, len: usize) {
+        if let Some(hook) = hook() {
+            hook.on_encode_response(name, len);
+        }
+    }
+
+    /// Report a decoded response to the global hook. Used by [`response!`](crate::response).
+    #[doc(hidden)]
+    pub fn decoded_response(name: &'static str, len: usize) {
+        if let Some(hook) = hook() {
+            hook.on_decode_response(name, len);
+        }
+    }
+}
+
+#[cfg(all(
+    test,
+    feature = "postcard",
+    any(feature = "checker", feature = "harness")
+))]
+mod tests {
+    use super::*;
+    use crate::fs::{Close, FdResult};
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Bytes of encoded and decoded `Close` commands and `FdResult` responses,
+    /// ignoring other types that tests running in parallel may encode.
+    #[derive(Default)]
+    struct Counter([AtomicUsize; 4]);
+
+    impl Counter {
+        fn counts(&self) -> [usize; 4] {
+            self.0.each_ref().map(|count| count.load(Ordering::Relaxed))
+        }
+    }
+
+    impl Hook for Counter {
+        fn on_encode(&self, id: usize, len: usize) {
+            if id == Close::ID {
+                self.0[0].fetch_add(len, Ordering::Relaxed);
+            }
+        }
+
+        fn on_decode(&self, id: usize, len: usize) {
+            if id == Close::ID {
+                self.0[1].fetch_add(len, Ordering::Relaxed);
+            }
+        }
+
+        fn on_encode_response(&self, name: &'static str, len: usize) {
+            if name == "FdResult" {
+                self.0[2].fetch_add(len, Ordering::Relaxed);
+            }
+        }
+
+        fn on_decode_response(&self, name: &'static str, len: usize) {
+            if name == "FdResult" {
+                self.0[3].fetch_add(len, Ordering::Relaxed);
+            }
+        }
+    }
+
+    #[cfg(feature = "checker")]
+    #[test]
+    fn checker_hooks() {
+        static COUNTER: Counter = Counter([const { AtomicUsize::new(0) }; 4]);
+        set_hook(&COUNTER);
+        let cmd = Close::new(3).to_bytes();
+        let resp = FdResult::new(-1).to_bytes();
+        let (decoded, _) = FdResult::from_bytes(&resp).unwrap();
+        assert_eq!(decoded.fd, -1);
+        assert_eq!(COUNTER.counts(), [cmd.len(), 0, resp.len(), resp.len()]);
+    }
+
+    #[cfg(feature = "harness")]
+    #[test]
+    fn harness_hooks() {
+        let counter = Counter::default();
+        // Version, payload length and fd.
+        let data = [1, 1, 6];
+        let (cmd, _) = Close::from_bytes_hooked(&data, &counter).unwrap();
+        assert_eq!(cmd.fd, 3);
+        let mut buf = [0; 16];
+        let len = FdResult::new(4)
+            .to_slice_hooked(&mut buf, &counter)
+            .unwrap()
+            .len();
+        assert_eq!(counter.counts(), [0, data.len(), len, 0]);
+    }
+}
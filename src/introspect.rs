@@ -0,0 +1,205 @@
+use crate::{
+    command,
+    envelope::TaskId,
+    fs::{FileKind, OpenFlags, Path},
+    mem::{MmapFlags, ProtFlags},
+    response,
+    validate::Validate,
+};
+use core::ops::Range;
+use serde::{Deserialize, Serialize};
+
+/// Max number of regions in one [`VmRegions`] response.
+pub const MAX_VM_REGIONS: usize = 64;
+
+/// Max number of entries in one [`FdTable`] response.
+pub const MAX_FD_ENTRIES: usize = 16;
+
+/// Max number of tasks in one [`TaskList`] response.
+pub const MAX_TASKS: usize = 64;
+
+/// Command ids reserved for introspection commands, through which a
+/// cooperating harness reports the internal state of the kernel under test.
+/// The range lies inside [`CONTROL_IDS`](crate::control::CONTROL_IDS).
+pub const INTROSPECT_IDS: Range<usize> = 0xf100..0xf200;
+
+/// Check if `id` is reserved for introspection commands.
+pub fn is_introspect_id(id: usize) -> bool {
+    INTROSPECT_IDS.contains(&id)
+}
+
+command!(
+    /// Query the memory regions of the current task. The list may be larger
+    /// than one response, so it is read in chunks starting at region `start`.
+    struct QueryVmRegions {
+        /// Index of the first region to return.
+        start: u32,
+    },
+    0xf100,
+    VmRegions
+);
+
+command!(
+    /// Query the open file descriptors of the current task, in chunks
+    /// starting at entry `start`.
+    struct QueryFdTable {
+        /// Index of the first entry to return.
+        start: u32,
+    },
+    0xf101,
+    FdTable
+);
+
+command!(
+    /// Query the tasks known to the kernel, in chunks starting at task `start`.
+    struct QueryTaskList {
+        /// Index of the first task to return.
+        start: u32,
+    },
+    0xf102,
+    TaskList
+);
+
+impl Validate for QueryVmRegions {}
+
+impl Validate for QueryFdTable {}
+
+impl Validate for QueryTaskList {}
+
+/// A mapped memory region, like a line of `/proc/self/maps`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct VmRegion {
+    /// Start address, page aligned.
+    pub start: u64,
+    /// End address (exclusive), page aligned.
+    pub end: u64,
+    /// Access permissions.
+    pub prot: ProtFlags,
+    /// Mapping flags, `MAP_FIXED` excluded.
+    pub flags: MmapFlags,
+    /// Offset in the backing file, zero for anonymous mappings.
+    pub offset: u64,
+}
+
+impl VmRegion {
+    /// Size of the region in bytes.
+    pub fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    /// Check if the region is empty.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Check if `addr` lies inside the region.
+    pub fn contains(&self, addr: u64) -> bool {
+        (self.start..self.end).contains(&addr)
+    }
+}
+
+/// An open file descriptor.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FdEntry {
+    /// The file descriptor.
+    pub fd: i64,
+    /// Kind of the open file.
+    pub kind: FileKind,
+    /// Status flags of the open file description.
+    pub flags: OpenFlags,
+    /// Current file offset.
+    pub offset: u64,
+    /// Path of the open file, empty if unknown.
+    pub path: Path,
+}
+
+/// Scheduling state of a task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TaskState {
+    /// Running or ready to run.
+    Running,
+    /// Blocked waiting for an event.
+    Sleeping,
+    /// Stopped by a signal or a tracer.
+    Stopped,
+    /// Exited but not yet reaped by its parent.
+    Zombie,
+}
+
+/// A task of the kernel under test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TaskInfo {
+    /// Harness task id, if the task was created by the harness.
+    pub task: Option<TaskId>,
+    /// Kernel thread id.
+    pub tid: u32,
+    /// Kernel process id.
+    pub pid: u32,
+    /// Process id of the parent.
+    pub ppid: u32,
+    /// Scheduling state.
+    pub state: TaskState,
+}
+
+response!(
+    /// Result of [`QueryVmRegions`]: a chunk of the region list, sorted by
+    /// start address.
+    struct VmRegions {
+        /// Index of the first region of the chunk.
+        start: u32,
+        /// Total number of regions.
+        total: u32,
+        /// The chunk.
+        regions: heapless::Vec<VmRegion, MAX_VM_REGIONS>,
+    }
+);
+
+response!(
+    /// Result of [`QueryFdTable`]: a chunk of the fd table, sorted by fd.
+    struct FdTable {
+        /// Index of the first entry of the chunk.
+        start: u32,
+        /// Total number of entries.
+        total: u32,
+        /// The chunk.
+        entries: heapless::Vec<FdEntry, MAX_FD_ENTRIES>,
+    }
+);
+
+response!(
+    /// Result of [`QueryTaskList`]: a chunk of the task list, sorted by tid.
+    struct TaskList {
+        /// Index of the first task of the chunk.
+        start: u32,
+        /// Total number of tasks.
+        total: u32,
+        /// The chunk.
+        tasks: heapless::Vec<TaskInfo, MAX_TASKS>,
+    }
+);
+
+impl VmRegions {
+    /// Check if this is the last chunk of the list.
+    pub fn is_last(&self) -> bool {
+        self.start as usize + self.regions.len() >= self.total as usize
+    }
+}
+
+impl FdTable {
+    /// Check if this is the last chunk of the table.
+    pub fn is_last(&self) -> bool {
+        self.start as usize + self.entries.len() >= self.total as usize
+    }
+
+    /// Entry of `fd`, if it is in this chunk.
+    pub fn get(&self, fd: i64) -> Option<&FdEntry> {
+        self.entries.iter().find(|entry| entry.fd == fd)
+    }
+}
+
+impl TaskList {
+    /// Check if this is the last chunk of the list.
+    pub fn is_last(&self) -> bool {
+        self.start as usize + self.tasks.len() >= self.total as usize
+    }
+}
@@ -1,7 +1,33 @@
 //! Command Defination of Kernel Model Check.
+//!
+//! # Wire format
+//!
+//! All multi-byte fixed-width values are little-endian, whatever the byte
+//! order of checker and harness. A command message is the command id as a
+//! `u32` (see `id_to_bytes`) followed by the encoded command:
+//!
+//! ```text
+//! [id: u32 LE][version: u8][payload length: varint][payload: postcard]
+//! ```
+//!
+//! Postcard encodes integers as little-endian varints, and `usize`/`isize`
+//! fields as 64-bit values. Transports add their own framing on top.
+//!
+//! With the `fixed-wire` feature, commands can instead be encoded as
+//! fixed-size records with every field at a constant offset, see [`fixed`].
 
-// Harness runs on no_std environment
-#![cfg_attr(feature = "harness", no_std)]
+// Harness runs on bare metal and checker may run inside another kernel,
+// so neither role depends on std. Checker only needs `alloc`.
+#![no_std]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(feature = "checker")]
+use alloc::vec::Vec;
 
 // Check features
 #[cfg(all(feature = "checker", feature = "harness"))]
@@ -16,34 +42,241 @@ pub mod mem;
 /// Filesystem-related commands.
 pub mod fs;
 
-/// Define a command with a fixed command id. Implement `Debug`,
-/// `Serialize`, and `Deserialize` for the command.
+/// Control commands handled by the harness itself.
+pub mod control;
+
+/// Kernel-state introspection commands.
+pub mod introspect;
+
+/// Comparison of kernel results against the model.
+pub mod compare;
+
+/// Reference models of kernel state.
+#[cfg(feature = "checker")]
+pub mod model;
+
+/// Semantic validation of command arguments.
+pub mod validate;
+
+/// Fixed-capacity byte and string buffers.
+pub mod bytes;
+
+/// Time types shared by time-related commands.
+pub mod time;
+
+/// Maximum encoded sizes of commands.
+pub mod size;
+
+/// Observability hooks on command encoding and decoding.
+pub mod hook;
+
+/// Per-command statistics for checker summaries.
+#[cfg(feature = "checker")]
+pub mod stats;
+
+/// Per-command metadata and replies.
+pub mod envelope;
+
+/// Type-erased commands for checker-side pipelines.
+#[cfg(feature = "checker")]
+pub mod erased;
+
+/// Shell equivalents of commands.
+#[cfg(feature = "checker")]
+pub mod shell;
+
+/// Machine-readable schema of all commands.
+#[cfg(feature = "checker")]
+pub mod schema;
+
+/// Dispatching of received commands to handlers.
+pub mod dispatch;
+
+/// Transports between checker and harness.
+pub mod transport;
+
+/// Shared-memory mailbox between checker and harness.
+pub mod mailbox;
+
+/// Lock-free SPSC ring buffer channel.
+pub mod ring;
+
+/// COBS framing for raw serial links.
+#[cfg(feature = "cobs")]
+pub mod cobs;
+
+/// CRC-32 integrity protection of encoded commands.
+#[cfg(feature = "crc")]
+pub mod crc;
+
+/// C header generation for C harnesses.
+#[cfg(feature = "cheader")]
+pub mod cheader;
+
+/// Sample values for macro-generated round-trip checks.
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
+
+/// Fixed-size command records.
+#[cfg(feature = "fixed-wire")]
+pub mod fixed;
+
+/// Define a command with a fixed command id. Implement `Debug`, `Clone`,
+/// `PartialEq`, `Eq`, `Hash`, `Serialize`, and `Deserialize` for the command.
 ///
 /// If a serialization format is specified, methods that serialize
 /// the command into a byte array and deserialize the command from
 /// a byte array are also implemented.
 ///
-/// Format: `command!(struct $name { ... }, $id)`
+/// If a response type is given, the command implements [`HasResponse`]
+/// with it.
+///
+/// Fields added after the first version of a command are marked with
+/// `#[km(since = N)]`, or `#[km(since = N, default)]` if the field should
+/// take its `Default` value when decoding data of an older version, or
+/// `#[km(since = N, default = V)]` to take the literal `V` instead. Such
+/// fields must be declared after all fields of older versions. The wire
+/// format carries the command version and payload length, so checker and
+/// harness built from adjacent versions can still understand each other.
+///
+/// Rarely used fields, typically `Option<T>`, are marked with `#[km(default)]`.
+/// Trailing default fields holding their `Default` value are left out of the
+/// encoded command, and take that value again when decoded.
+///
+/// Format: `command!(struct $name { ... }, $id [, $response])`
 #[macro_export]
 macro_rules! command {
     (
         $(#[$outer:meta])*
         struct $name:ident $(<$lt:lifetime>)? {
-            $($(#[$attr:meta])* $field:ident: $t:ty,)*
+            $($body:tt)*
         },
         $id:literal
+        $(, $resp:ty)?
+    ) => {
+        $crate::__command! {
+            @parse [[$(#[$outer])*] [$name $(<$lt>)?] [$id] [$($resp)?]]
+            [] [] []
+            $($body)*
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __command {
+    // `@parse [context] [parsed fields] [field attributes] [field version] body...`
+    (
+        @parse $ctx:tt [$($fields:tt)*] [$($attrs:tt)*] []
+        #[km(since = $since:literal, default = $value:literal)] $($rest:tt)*
+    ) => {
+        $crate::__command! {
+            @parse $ctx [$($fields)*] [$($attrs)*] [$since, default = $value] $($rest)*
+        }
+    };
+    (
+        @parse $ctx:tt [$($fields:tt)*] [$($attrs:tt)*] []
+        #[km(since = $since:literal, default)] $($rest:tt)*
+    ) => {
+        $crate::__command! {
+            @parse $ctx [$($fields)*] [$($attrs)*] [$since, default] $($rest)*
+        }
+    };
+    (
+        @parse $ctx:tt [$($fields:tt)*] [$($attrs:tt)*] []
+        #[km(default)] $($rest:tt)*
+    ) => {
+        $crate::__command! {
+            @parse $ctx [$($fields)*] [$($attrs)*] [1, default] $($rest)*
+        }
+    };
+    (
+        @parse $ctx:tt [$($fields:tt)*] [$($attrs:tt)*] []
+        #[km(since = $since:literal)] $($rest:tt)*
+    ) => {
+        $crate::__command! {
+            @parse $ctx [$($fields)*] [$($attrs)*] [$since] $($rest)*
+        }
+    };
+    (
+        @parse $ctx:tt [$($fields:tt)*] [$($attrs:tt)*] [$($km:tt)*]
+        #[$attr:meta] $($rest:tt)*
+    ) => {
+        $crate::__command! {
+            @parse $ctx [$($fields)*] [$($attrs)* #[$attr]] [$($km)*] $($rest)*
+        }
+    };
+    (
+        @parse $ctx:tt [$($fields:tt)*] [$($attrs:tt)*] []
+        $field:ident: $t:ty, $($rest:tt)*
+    ) => {
+        $crate::__command! {
+            @parse $ctx [$($fields)* {[$($attrs)*] [1] $field: $t}] [] [] $($rest)*
+        }
+    };
+    (
+        @parse $ctx:tt [$($fields:tt)*] [$($attrs:tt)*] [$($km:tt)+]
+        $field:ident: $t:ty, $($rest:tt)*
+    ) => {
+        $crate::__command! {
+            @parse $ctx [$($fields)* {[$($attrs)*] [$($km)+] $field: $t}] [] [] $($rest)*
+        }
+    };
+    (@parse $ctx:tt [$($fields:tt)*] [] []) => {
+        $crate::__command! { @emit $ctx [$($fields)*] }
+    };
+    (
+        @emit [[$(#[$outer:meta])*] [$name:ident $(<$lt:lifetime>)?] [$id:literal] [$($resp:ty)?]]
+        [$({[$(#[$attr:meta])*] [$since:literal $(, $default:ident $(= $value:literal)?)?] $field:ident: $t:ty})*]
     ) => {
         $(#[$outer])*
-        #[derive(Debug, Serialize, Deserialize)]
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
         #[repr(C)]
         pub struct $name$(<$lt>)? {
             $($(#[$attr])* pub $field: $t),*
         }
 
+        #[allow(clippy::new_without_default)]
         impl$(<$lt>)? $name$(<$lt>)? {
             /// Command id.
             pub const ID: usize = $id;
 
+            /// Command name, i.e. the lowercase struct name.
+            pub const NAME: &'static str = {
+                const BYTES: [u8; stringify!($name).len()] =
+                    $crate::__ascii_lowercase(stringify!($name));
+                match ::core::str::from_utf8(&BYTES) {
+                    Ok(name) => name,
+                    Err(_) => panic!("command name is not valid UTF-8"),
+                }
+            };
+
+            /// Command version, i.e. the latest version of its fields.
+            pub const VERSION: u8 = $crate::__max_version(&[1 $(, $since)*]);
+
+            /// Maximum size of the encoded command, excluding its id.
+            pub const MAX_ENCODED_SIZE: usize = $crate::size::frame_size(
+                0 $(+ <$t as $crate::size::MaxSize>::MAX_SIZE)*
+            );
+
+            /// Whether the command has a typed response.
+            pub const HAS_RESPONSE: bool = $crate::__has_response!($($resp)?);
+
+            /// Static description of the command.
+            pub const DESC: $crate::CommandDesc = $crate::CommandDesc {
+                id: $id,
+                name: Self::NAME,
+                module: module_path!(),
+                version: Self::VERSION,
+                has_response: Self::HAS_RESPONSE,
+                fields: &[$($crate::FieldDesc {
+                    name: stringify!($field),
+                    ty: stringify!($t),
+                    since: $since,
+                    default: $crate::__is_default!($($default $(= $value)?)?),
+                }),*],
+            };
+
             /// Create a new command.
             pub fn new($($field: $t),*) -> Self {
                 Self {
@@ -52,21 +285,494 @@ macro_rules! command {
             }
         }
 
+        $crate::__impl_has_response!([$name $(<$lt>)?] $($resp)?);
+
+        #[cfg(feature = "fixed-wire")]
+        impl$(<$lt>)? $name$(<$lt>)? {
+            /// Size of the fields in a fixed-size record, see [`fixed`](crate::fixed).
+            pub const FIXED_SIZE: usize =
+                0 $(+ <$t as $crate::fixed::FixedField>::SIZE)*;
+        }
+
+        #[cfg(feature = "postcard")]
+        #[allow(dead_code)]
+        impl$(<$lt>)? $name$(<$lt>)? {
+            /// Encoded size of the trailing default fields holding their default
+            /// value, which are left out of the encoded command.
+            #[allow(unused_mut, unused_assignments)]
+            fn trailing_default_size(&self) -> usize {
+                let mut size = 0;
+                $(size = $crate::__trim_field!(self.$field, $t, size $(, $default $(= $value)?)?);)*
+                size
+            }
+
+            /// Decode a command encoded by `encode_versioned_to_slice`.
+            fn decode_versioned(data: &[u8]) -> Option<(Self, &[u8])> {
+                #[allow(unused_mut, unused_variables)]
+                let (mut decoder, rest) = $crate::VersionedDecoder::new(data)?;
+                $(
+                    let $field = match decoder.field::<$t>($since)? {
+                        Some(value) => value,
+                        None => $crate::__field_fallback!($($default $(= $value)?)?),
+                    };
+                )*
+                Some((Self { $($field,)* }, rest))
+            }
+
+            /// Encode commands built from [`Sample`](crate::test_utils::Sample)
+            /// values and decode them back, panicking if the result differs or
+            /// exceeds `MAX_ENCODED_SIZE`. The second command keeps the fields
+            /// marked `default` at their default value, so that they are trimmed.
+            #[cfg(feature = "test-utils")]
+            pub fn check_roundtrip() {
+                let cmd = Self::new($(<$t as $crate::test_utils::Sample>::sample()),*);
+                cmd.check_sample_roundtrip("sample");
+                let cmd = Self::new($($crate::__sample_or_default!($t $(, $default $(= $value)?)?)),*);
+                cmd.check_sample_roundtrip("defaults");
+            }
+
+            #[cfg(feature = "test-utils")]
+            fn check_sample_roundtrip(&self, sample: &str) {
+                let mut buf = [0; $crate::test_utils::MAX_SAMPLE_SIZE];
+                let trim = self.trailing_default_size();
+                let bytes = $crate::encode_versioned_to_slice(Self::VERSION, self, trim, &mut buf)
+                    .unwrap_or_else(|| panic!("{} ({}): failed to encode {:?}", Self::NAME, sample, self));
+                assert!(
+                    bytes.len() <= Self::MAX_ENCODED_SIZE,
+                    "{} ({}): encoded size {} exceeds MAX_ENCODED_SIZE {}",
+                    Self::NAME,
+                    sample,
+                    bytes.len(),
+                    Self::MAX_ENCODED_SIZE,
+                );
+                let (decoded, rest) = Self::decode_versioned(bytes).unwrap_or_else(|| {
+                    panic!("{} ({}): failed to decode {:?}", Self::NAME, sample, bytes)
+                });
+                assert!(rest.is_empty(), "{} ({}): {} trailing bytes", Self::NAME, sample, rest.len());
+                assert_eq!(&decoded, self, "{} ({}): decoded command differs", Self::NAME, sample);
+            }
+        }
+
         #[cfg(feature = "checker")]
         impl$(<$lt>)? $name$(<$lt>)? {
             /// Serialize the command into a byte array
-            pub fn to_bytes(&self) -> Vec<u8> {
+            pub fn to_bytes(&self) -> $crate::__private::Vec<u8> {
+                let mut bytes = $crate::__private::Vec::new();
+                self.encode_into(&mut bytes);
+                bytes
+            }
+
+            /// Serialize the command and append it to `buf`, reusing its allocation.
+            pub fn encode_into(&self, buf: &mut $crate::__private::Vec<u8>) {
                 if cfg!(feature = "postcard") {
-                    postcard::to_allocvec(self).unwrap()
+                    let start = buf.len();
+                    let trim = self.trailing_default_size();
+                    $crate::encode_versioned_into(Self::VERSION, self, trim, buf);
+                    $crate::hook::encoded(Self::ID, buf.len() - start);
+                }
+            }
+
+            /// Serialize the command into `buf` without allocating, return the
+            /// used part of `buf`, or `None` if `buf` is too small.
+            pub fn to_slice<'b>(&self, buf: &'b mut [u8]) -> Option<&'b mut [u8]> {
+                if cfg!(feature = "postcard") {
+                    let trim = self.trailing_default_size();
+                    let bytes = $crate::encode_versioned_to_slice(Self::VERSION, self, trim, buf)?;
+                    $crate::hook::encoded(Self::ID, bytes.len());
+                    Some(bytes)
                 } else {
-                    Vec::new()
+                    None
                 }
             }
+
+            /// Serialize the command into a byte array followed by its CRC-32.
+            #[cfg(feature = "crc")]
+            pub fn to_bytes_crc32(&self) -> $crate::__private::Vec<u8> {
+                $crate::crc::append_crc32(self.to_bytes())
+            }
+
+            /// Serialize the command with its id into a fixed-size record.
+            #[cfg(feature = "fixed-wire")]
+            #[allow(unused_variables)]
+            pub fn to_fixed(&self) -> $crate::fixed::Record {
+                let mut record = [0; $crate::fixed::RECORD_SIZE];
+                let buf = $crate::fixed::__write_id(&mut record, Self::ID);
+                $(let buf = $crate::fixed::__write_field(&self.$field, buf);)*
+                record
+            }
+        }
+
+        #[cfg(feature = "checker")]
+        impl$(<$lt>)? $crate::Encode for $name$(<$lt>)? {
+            const ID: usize = $id;
+            const NAME: &'static str = <$name>::NAME;
+
+            fn encode_into(&self, buf: &mut $crate::__private::Vec<u8>) {
+                self.encode_into(buf)
+            }
         }
 
         #[cfg(feature = "harness")]
         impl$(<$lt>)? $name$(<$lt>)? {
             /// Deserialize the command from a byte array, return the command and the remaining data.
+            pub fn from_bytes(data: &[u8]) -> Option<(Self, &[u8])> {
+                if cfg!(feature = "postcard") {
+                    Self::decode_versioned(data)
+                } else {
+                    None
+                }
+            }
+
+            /// Like `from_bytes`, but report the decoded command to `hook`.
+            pub fn from_bytes_hooked<'d, H: $crate::hook::Hook>(
+                data: &'d [u8],
+                hook: &H,
+            ) -> Option<(Self, &'d [u8])> {
+                let (cmd, rest) = Self::from_bytes(data)?;
+                hook.on_decode(Self::ID, data.len() - rest.len());
+                Some((cmd, rest))
+            }
+
+            /// Deserialize the command from a byte array produced by `to_bytes_crc32`,
+            /// return the command and the remaining data. Return `None` if the CRC-32
+            /// does not match.
+            #[cfg(feature = "crc")]
+            pub fn from_bytes_crc32(data: &[u8]) -> Option<(Self, &[u8])> {
+                let (frame, rest) = $crate::crc::split_frame(data)?;
+                let (cmd, _) = Self::from_bytes(frame)?;
+                Some((cmd, rest))
+            }
+
+            /// Deserialize the command from the first fixed-size record of `data`,
+            /// return the command and the remaining data. Return `None` if the
+            /// record holds another command.
+            #[cfg(feature = "fixed-wire")]
+            #[allow(unused_variables)]
+            pub fn from_fixed(data: &[u8]) -> Option<(Self, &[u8])> {
+                let (id, fields, rest) = $crate::fixed::split_record(data)?;
+                if id != Self::ID {
+                    return None;
+                }
+                let buf = fields;
+                $(let ($field, buf) = $crate::fixed::__read_field::<$t>(buf)?;)*
+                Some((Self { $($field,)* }, rest))
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __is_default {
+    () => {
+        false
+    };
+    (default $(= $value:literal)?) => {
+        true
+    };
+}
+
+/// Size of the trailing default fields up to and including `$value`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __trim_field {
+    ($value:expr, $t:ty, $size:ident) => {
+        0
+    };
+    ($value:expr, $t:ty, $size:ident, default) => {
+        $crate::__trim_field!($value, $t, $size, default = (<$t as Default>::default()))
+    };
+    ($value:expr, $t:ty, $size:ident, default = $default:expr) => {
+        if $value == $default {
+            $size + $crate::__serialized_size(&$value)
+        } else {
+            0
+        }
+    };
+}
+
+/// Encoded size of `value`.
+#[doc(hidden)]
+#[cfg(feature = "postcard")]
+pub fn __serialized_size<T: Serialize>(value: &T) -> usize {
+    postcard::experimental::serialized_size(value).unwrap()
+}
+
+/// Sample value of a field, or its default value if it is marked `default`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __sample_or_default {
+    ($t:ty) => {
+        <$t as $crate::test_utils::Sample>::sample()
+    };
+    ($t:ty, default) => {
+        <$t as Default>::default()
+    };
+    ($t:ty, default = $value:literal) => {
+        $value
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __field_fallback {
+    () => {
+        return None
+    };
+    (default) => {
+        Default::default()
+    };
+    (default = $value:literal) => {
+        $value
+    };
+}
+
+/// Latest version among the fields of a command. Used by [`command!`].
+#[doc(hidden)]
+pub const fn __max_version(versions: &[u8]) -> u8 {
+    let mut max = 0;
+    let mut i = 0;
+    while i < versions.len() {
+        if versions[i] > max {
+            max = versions[i];
+        }
+        i += 1;
+    }
+    max
+}
+
+/// Encode a command as `[version, payload length (varint), payload]`.
+#[cfg(all(feature = "checker", feature = "postcard"))]
+pub fn encode_versioned<T: Serialize>(version: u8, cmd: &T) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    encode_versioned_into(version, cmd, 0, &mut bytes);
+    bytes
+}
+
+/// Like [`encode_versioned`], but append to `buf`, reusing its allocation.
+/// The last `trim` bytes of the payload are left out.
+#[cfg(all(feature = "checker", feature = "postcard"))]
+pub fn encode_versioned_into<T: Serialize>(version: u8, cmd: &T, trim: usize, buf: &mut Vec<u8>) {
+    let len = postcard::experimental::serialized_size(cmd).unwrap() - trim;
+    buf.push(version);
+    let bytes = postcard::to_extend(&len, core::mem::take(buf)).unwrap();
+    *buf = postcard::to_extend(cmd, bytes).unwrap();
+    buf.truncate(buf.len() - trim);
+}
+
+/// A command that can be encoded by an [`Encoder`].
+#[cfg(feature = "checker")]
+pub trait Encode {
+    /// Command id.
+    const ID: usize;
+
+    /// Command name.
+    const NAME: &'static str;
+
+    /// Append the encoded command to `buf`.
+    fn encode_into(&self, buf: &mut Vec<u8>);
+}
+
+/// Encoder reusing one buffer across commands, avoiding an allocation per
+/// command when replaying long traces.
+#[cfg(feature = "checker")]
+#[derive(Debug, Default)]
+pub struct Encoder {
+    buf: Vec<u8>,
+}
+
+#[cfg(feature = "checker")]
+impl Encoder {
+    /// Create an encoder with an empty buffer.
+    pub const fn new() -> Self {
+        Self { buf: Vec::new() }
+    }
+
+    /// Create an encoder whose buffer can hold any command without growing.
+    pub fn with_max_capacity() -> Self {
+        Self {
+            buf: Vec::with_capacity(MAX_COMMAND_SIZE),
+        }
+    }
+
+    /// Encode `cmd`, return the encoded command. The result is valid until
+    /// the next call.
+    pub fn encode<C: Encode>(&mut self, cmd: &C) -> &[u8] {
+        self.buf.clear();
+        cmd.encode_into(&mut self.buf);
+        &self.buf
+    }
+
+    /// Encode the type-erased command `cmd`.
+    pub fn encode_erased(&mut self, cmd: &dyn erased::ErasedCommand) -> &[u8] {
+        self.buf.clear();
+        cmd.encode(&mut self.buf);
+        &self.buf
+    }
+
+    /// Encode `cmd` preceded by its id, as written by `id_to_bytes`.
+    pub fn encode_with_id<C: Encode>(&mut self, cmd: &C) -> &[u8] {
+        self.buf.clear();
+        self.buf.extend_from_slice(&id_to_wire(C::ID));
+        cmd.encode_into(&mut self.buf);
+        &self.buf
+    }
+}
+
+/// Like `encode_versioned_into`, but write into `buf` instead of allocating.
+/// Return the used part of `buf`, or `None` if `buf` is too small.
+#[cfg(feature = "postcard")]
+pub fn encode_versioned_to_slice<'b, T: Serialize>(
+    version: u8,
+    cmd: &T,
+    trim: usize,
+    buf: &'b mut [u8],
+) -> Option<&'b mut [u8]> {
+    let len = postcard::experimental::serialized_size(cmd).ok()? - trim;
+    let (header, payload) = buf.split_first_mut()?;
+    *header = version;
+    let len_size = postcard::to_slice(&len, payload).ok()?.len();
+    let used = postcard::to_slice(cmd, &mut payload[len_size..])
+        .ok()?
+        .len();
+    Some(&mut buf[..1 + len_size + used - trim])
+}
+
+/// Decoder of a command payload encoded by [`encode_versioned`].
+#[cfg(feature = "postcard")]
+pub struct VersionedDecoder<'a> {
+    version: u8,
+    payload: &'a [u8],
+}
+
+#[cfg(feature = "postcard")]
+impl<'a> VersionedDecoder<'a> {
+    /// Split a versioned payload off `data`, return the decoder and the remaining data.
+    pub fn new(data: &'a [u8]) -> Option<(Self, &'a [u8])> {
+        let (&version, data) = data.split_first()?;
+        let (len, data) = postcard::take_from_bytes::<usize>(data).ok()?;
+        if version == 0 || data.len() < len {
+            return None;
+        }
+        let (payload, rest) = data.split_at(len);
+        Some((Self { version, payload }, rest))
+    }
+
+    /// Version of the encoded command.
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Decode the next field, which was added in version `since`.
+    ///
+    /// Return `Some(None)` if the field is newer than the encoded command or
+    /// was left out at the end of the payload, and `None` if the payload is
+    /// malformed.
+    pub fn field<T: Deserialize<'a>>(&mut self, since: u8) -> Option<Option<T>> {
+        if since > self.version || self.payload.is_empty() {
+            return Some(None);
+        }
+        let (value, payload) = postcard::take_from_bytes::<T>(self.payload).ok()?;
+        self.payload = payload;
+        Some(Some(value))
+    }
+}
+
+/// Define a response to a command. Implement `Debug`, `Clone`,
+/// `PartialEq`, `Eq`, `Hash`, `Serialize`, and `Deserialize` for the response.
+///
+/// Responses are encoded with the same serialization format as
+/// commands, so that the harness and the checker agree on how
+/// replies look on the wire.
+///
+/// Format: `response!(struct $name { ... })`
+#[macro_export]
+macro_rules! response {
+    (
+        $(#[$outer:meta])*
+        struct $name:ident {
+            $($(#[$attr:meta])* $field:ident: $t:ty,)*
+        }
+    ) => {
+        $(#[$outer])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        pub struct $name {
+            $($(#[$attr])* pub $field: $t),*
+        }
+
+        impl $name {
+            /// Response name, i.e. the struct name.
+            pub const NAME: &'static str = stringify!($name);
+
+            /// Create a new response.
+            pub fn new($($field: $t),*) -> Self {
+                Self {
+                    $($field,)*
+                }
+            }
+        }
+
+        #[cfg(feature = "checker")]
+        impl $name {
+            /// Serialize the response into a byte array.
+            pub fn to_bytes(&self) -> $crate::__private::Vec<u8> {
+                if cfg!(feature = "postcard") {
+                    let bytes = postcard::to_allocvec(self).unwrap();
+                    $crate::hook::encoded_response(Self::NAME, bytes.len());
+                    bytes
+                } else {
+                    $crate::__private::Vec::new()
+                }
+            }
+
+            /// Deserialize the response from a byte array, return the response and the remaining data.
+            pub fn from_bytes(data: &[u8]) -> Option<(Self, &[u8])> {
+                if cfg!(feature = "postcard") {
+                    let (resp, rest) = postcard::take_from_bytes::<Self>(data).ok()?;
+                    $crate::hook::decoded_response(Self::NAME, data.len() - rest.len());
+                    Some((resp, rest))
+                } else {
+                    None
+                }
+            }
+        }
+
+        #[cfg(feature = "harness")]
+        impl $name {
+            /// Serialize the response into `buf` without allocating, return the
+            /// used part of `buf`, or `None` if `buf` is too small.
+            pub fn to_slice<'b>(&self, buf: &'b mut [u8]) -> Option<&'b mut [u8]> {
+                if cfg!(feature = "postcard") {
+                    postcard::to_slice(self, buf).ok()
+                } else {
+                    None
+                }
+            }
+
+            /// Like `to_slice`, but report the encoded response to `hook`.
+            pub fn to_slice_hooked<'b, H: $crate::hook::Hook>(
+                &self,
+                buf: &'b mut [u8],
+                hook: &H,
+            ) -> Option<&'b mut [u8]> {
+                let bytes = self.to_slice(buf)?;
+                hook.on_encode_response(Self::NAME, bytes.len());
+                Some(bytes)
+            }
+
+            /// Serialize the response into a vector of capacity `N`, or return
+            /// `None` if it does not fit.
+            pub fn to_heapless_vec<const N: usize>(
+                &self,
+            ) -> Option<$crate::__private::heapless::Vec<u8, N>> {
+                let mut bytes = $crate::__private::heapless::Vec::new();
+                bytes.resize_default(N).ok()?;
+                let len = self.to_slice(&mut bytes)?.len();
+                bytes.truncate(len);
+                Some(bytes)
+            }
+
+            /// Deserialize the response from a byte array, return the response and the remaining data.
             pub fn from_bytes(data: &[u8]) -> Option<(Self, &[u8])> {
                 if cfg!(feature = "postcard") {
                     postcard::take_from_bytes::<Self>(data).ok()
@@ -78,17 +784,219 @@ macro_rules! command {
     };
 }
 
+/// Lowercase an ASCII string at compile time. Used by [`command!`].
+#[doc(hidden)]
+pub const fn __ascii_lowercase<const N: usize>(s: &str) -> [u8; N] {
+    let bytes = s.as_bytes();
+    let mut out = [0; N];
+    let mut i = 0;
+    while i < N {
+        out[i] = bytes[i].to_ascii_lowercase();
+        i += 1;
+    }
+    out
+}
+
+/// Invoke `$m!` with the types of all defined commands.
+macro_rules! all_commands {
+    ($m:ident) => {
+        $m!(
+            Nop,
+            fs::Openat,
+            fs::Close,
+            fs::Fstat,
+            fs::Getdents,
+            fs::Getdents1,
+            fs::Linkat,
+            fs::Unlinkat,
+            fs::Mkdirat,
+            fs::Getcwd,
+            fs::Dup,
+            fs::Chdir,
+            mem::Brk,
+            mem::Sbrk,
+            mem::Mmap,
+            mem::Munmap,
+            mem::Mprotect,
+            control::Ping,
+            control::Reset,
+            control::GetCapabilities,
+            control::ShutdownHarness,
+            control::InjectFault,
+            control::QueryCoverage,
+            control::SetSeed,
+            control::SwitchTask,
+            control::AssertInvariant,
+            introspect::QueryVmRegions,
+            introspect::QueryFdTable,
+            introspect::QueryTaskList
+        )
+    };
+}
+
+macro_rules! command_descs {
+    ($($cmd:ty),*) => {
+        &[$(<$cmd>::DESC),*]
+    };
+}
+
+/// Static description of a command, generated by [`command!`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandDesc {
+    /// Command id.
+    pub id: usize,
+    /// Command name.
+    pub name: &'static str,
+    /// Path of the module defining the command, e.g. `km_command::fs`.
+    pub module: &'static str,
+    /// Command version.
+    pub version: u8,
+    /// Whether the command has a typed response.
+    pub has_response: bool,
+    /// Fields of the command in wire order.
+    pub fields: &'static [FieldDesc],
+}
+
+/// Static description of a command field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldDesc {
+    /// Field name.
+    pub name: &'static str,
+    /// Field type as written in the command definition.
+    pub ty: &'static str,
+    /// Command version in which the field was added.
+    pub since: u8,
+    /// Whether the field takes its `Default` value when absent.
+    pub default: bool,
+}
+
+macro_rules! command_max_size {
+    ($($cmd:ty),*) => {
+        size::max_of(&[$(<$cmd>::MAX_ENCODED_SIZE),*])
+    };
+}
+
+/// Maximum size of any encoded command, including the id written by
+/// `id_to_bytes`. A receive buffer of this size can hold any command.
+pub const MAX_COMMAND_SIZE: usize = ID_SIZE + all_commands!(command_max_size);
+
+#[cfg(feature = "fixed-wire")]
+macro_rules! command_fixed_size {
+    ($($cmd:ty),*) => {
+        size::max_of(&[$(<$cmd>::FIXED_SIZE),*])
+    };
+}
+
+/// Maximum size of the fields of any command in a fixed-size record.
+#[cfg(feature = "fixed-wire")]
+const MAX_FIXED_SIZE: usize = all_commands!(command_fixed_size);
+
+/// Descriptions of all defined commands.
+pub static COMMANDS: &[CommandDesc] = all_commands!(command_descs);
+
+/// A command with a typed response.
+pub trait HasResponse {
+    /// The response type.
+    type Response;
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __impl_has_response {
+    ([$name:ident $(<$lt:lifetime>)?]) => {};
+    ([$name:ident $(<$lt:lifetime>)?] $resp:ty) => {
+        impl$(<$lt>)? $crate::HasResponse for $name$(<$lt>)? {
+            type Response = $resp;
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __has_response {
+    () => {
+        false
+    };
+    ($resp:ty) => {
+        true
+    };
+}
+
+/// Get the name of the command with the given id.
+///
+/// If several commands share the same id (e.g. `brk` and `sbrk`),
+/// the name of the first defined one is returned.
+pub fn command_name(id: usize) -> Option<&'static str> {
+    COMMANDS
+        .iter()
+        .find(|desc| desc.id == id)
+        .map(|desc| desc.name)
+}
+
+/// Re-exports used by macros.
+#[doc(hidden)]
+pub mod __private {
+    pub use heapless;
+
+    #[cfg(feature = "alloc")]
+    pub use alloc::vec::Vec;
+}
+
+/// Size of a command id on the wire. Ids are written as `u32` whatever the
+/// pointer width, so a 64-bit checker can drive a 32-bit harness.
+pub const ID_SIZE: usize = core::mem::size_of::<u32>();
+
+/// Wire representation of command id `id`, a little-endian `u32`.
+pub const fn id_to_wire(id: usize) -> [u8; ID_SIZE] {
+    debug_assert!(id <= u32::MAX as usize, "command id does not fit in u32");
+    (id as u32).to_le_bytes()
+}
+
+/// Command id from its wire representation.
+pub const fn id_from_wire(bytes: [u8; ID_SIZE]) -> usize {
+    u32::from_le_bytes(bytes) as usize
+}
+
 /// Serialize command id.
 #[cfg(feature = "checker")]
 pub fn id_to_bytes(id: usize) -> Vec<u8> {
-    id.to_le_bytes().to_vec()
+    id_to_wire(id).to_vec()
 }
 
 /// Deserialize command id, return the id and the remaining data.
+///
+/// Fail with [`WireError::Truncated`] if `data` is shorter than an id.
 #[cfg(feature = "harness")]
-pub fn id_from_bytes(data: &[u8]) -> (usize, &[u8]) {
-    let id = usize::from_le_bytes(data[..core::mem::size_of::<usize>()].try_into().unwrap());
-    (id, &data[core::mem::size_of::<usize>()..])
+pub fn id_from_bytes(data: &[u8]) -> Result<(usize, &[u8]), WireError> {
+    let Some((id, rest)) = data.split_first_chunk::<ID_SIZE>() else {
+        return Err(WireError::Truncated {
+            needed: ID_SIZE,
+            got: data.len(),
+        });
+    };
+    Ok((id_from_wire(*id), rest))
+}
+
+/// Error decoding the framing of a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WireError {
+    /// The message is shorter than its fixed-size header.
+    Truncated {
+        /// Bytes needed.
+        needed: usize,
+        /// Bytes available.
+        got: usize,
+    },
+}
+
+impl core::fmt::Display for WireError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Truncated { needed, got } => {
+                write!(f, "truncated message: need {} bytes, got {}", needed, got)
+            }
+        }
+    }
 }
 
 use serde::{Deserialize, Serialize};
@@ -101,3 +1009,43 @@ command!(
     struct Nop {},
     65535
 );
+
+impl validate::Validate for Nop {}
+
+#[cfg(all(test, any(feature = "test-utils", feature = "cheader")))]
+mod tests {
+    use crate::*;
+
+    #[cfg(feature = "test-utils")]
+    macro_rules! check_roundtrips {
+        ($($cmd:ty),*) => {
+            $(<$cmd>::check_roundtrip();)*
+        };
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[test]
+    fn commands_roundtrip() {
+        all_commands!(check_roundtrips);
+    }
+
+    #[cfg(feature = "cheader")]
+    macro_rules! check_c_layouts {
+        ($($cmd:ty),*) => {
+            $(
+                let size: usize = <$cmd>::DESC
+                    .fields
+                    .iter()
+                    .map(|field| cheader::c_type(field.ty).unwrap().1)
+                    .sum();
+                assert_eq!(size, <$cmd>::FIXED_SIZE, "{}", <$cmd>::NAME);
+            )*
+        };
+    }
+
+    #[cfg(feature = "cheader")]
+    #[test]
+    fn c_layouts_match_fixed_encoding() {
+        all_commands!(check_c_layouts);
+    }
+}
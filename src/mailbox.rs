@@ -0,0 +1,239 @@
+//! The mailbox occupies a fixed memory region shared by checker and harness,
+//! e.g. a page reserved in the guest physical memory of QEMU:
+//!
+//! ```text
+//! +--------------------+ 0
+//! | MailboxHeader      |
+//! +--------------------+ MailboxHeader::SIZE
+//! | command area       |
+//! +--------------------+ MailboxHeader::SIZE + cmd_cap
+//! | response area      |
+//! +--------------------+ region length
+//! ```
+//!
+//! The checker writes a command message (command id followed by the encoded
+//! command) into the command area, stores its length and then increments
+//! `head`. The harness sees `head != tail`, handles the command, writes the
+//! response into the response area, stores its length and sets `tail` to
+//! `head`. At most one command is in flight. The checker remembers whether it
+//! has posted a command, so that `head == tail` reads as a response only once
+//! a command was sent.
+
+use core::{
+    mem::size_of,
+    slice,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+/// Header at the start of a mailbox region.
+#[repr(C)]
+pub struct MailboxHeader {
+    /// Sequence number of the latest command, incremented by the checker.
+    pub head: AtomicU32,
+    /// Sequence number of the latest response, set to `head` by the harness.
+    pub tail: AtomicU32,
+    /// Length of the command message in the command area.
+    pub cmd_len: AtomicU32,
+    /// Length of the response in the response area.
+    pub resp_len: AtomicU32,
+}
+
+impl MailboxHeader {
+    /// Size of the header in bytes.
+    pub const SIZE: usize = size_of::<Self>();
+}
+
+/// Mailbox over a shared memory region.
+pub struct Mailbox {
+    header: *const MailboxHeader,
+    cmd: *mut u8,
+    cmd_cap: usize,
+    resp: *mut u8,
+    resp_cap: usize,
+    /// Checker: whether a command has been posted since the last reset.
+    sent: bool,
+}
+
+impl Mailbox {
+    /// Create a mailbox over the region `[base, base + len)` with a command
+    /// area of `cmd_cap` bytes; the rest of the region is the response area.
+    ///
+    /// Return `None` if `base` is misaligned or the region is too small.
+    ///
+    /// # Safety
+    ///
+    /// The region must be valid for reads and writes for the lifetime of the
+    /// mailbox, and only be accessed by the peer through the mailbox protocol.
+    pub unsafe fn new(base: *mut u8, len: usize, cmd_cap: usize) -> Option<Self> {
+        if !(base as *const MailboxHeader).is_aligned() || len < MailboxHeader::SIZE + cmd_cap {
+            return None;
+        }
+        let cmd = base.add(MailboxHeader::SIZE);
+        Some(Self {
+            header: base as *const MailboxHeader,
+            cmd,
+            cmd_cap,
+            resp: cmd.add(cmd_cap),
+            resp_cap: len - MailboxHeader::SIZE - cmd_cap,
+            sent: false,
+        })
+    }
+
+    /// Zero the header, so that no command is pending.
+    pub fn reset(&mut self) {
+        let header = self.header();
+        header.head.store(0, Ordering::Relaxed);
+        header.tail.store(0, Ordering::Relaxed);
+        header.cmd_len.store(0, Ordering::Relaxed);
+        header.resp_len.store(0, Ordering::Release);
+        self.sent = false;
+    }
+
+    fn header(&self) -> &MailboxHeader {
+        // SAFETY: `new` checked that the header is aligned and within the
+        // region, which its caller guarantees to be valid for the lifetime of
+        // the mailbox; the header is only accessed through atomics.
+        unsafe { &*self.header }
+    }
+
+    /// Harness: return the pending command message, if any.
+    pub fn poll(&self) -> Option<&[u8]> {
+        let header = self.header();
+        if header.head.load(Ordering::Acquire) == header.tail.load(Ordering::Relaxed) {
+            return None;
+        }
+        let len = (header.cmd_len.load(Ordering::Relaxed) as usize).min(self.cmd_cap);
+        // SAFETY: the command area holds `cmd_cap` bytes of the region, and
+        // the checker does not write it while the command is pending.
+        Some(unsafe { slice::from_raw_parts(self.cmd, len) })
+    }
+
+    /// Harness: wait for a command message.
+    pub fn recv(&self) -> &[u8] {
+        loop {
+            if let Some(cmd) = self.poll() {
+                return cmd;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Harness: the response area, to encode a response into in place before
+    /// calling [`Mailbox::complete`].
+    pub fn response_buf(&mut self) -> &mut [u8] {
+        // SAFETY: the response area holds `resp_cap` bytes of the region, and
+        // the checker does not read it until the response is published.
+        unsafe { slice::from_raw_parts_mut(self.resp, self.resp_cap) }
+    }
+
+    /// Harness: publish the first `len` bytes of the response area as the
+    /// response to the pending command.
+    pub fn complete(&mut self, len: usize) {
+        let header = self.header();
+        header
+            .resp_len
+            .store(len.min(self.resp_cap) as u32, Ordering::Relaxed);
+        let head = header.head.load(Ordering::Relaxed);
+        header.tail.store(head, Ordering::Release);
+    }
+
+    /// Harness: copy `resp` into the response area and publish it. Return
+    /// `false` if it does not fit.
+    pub fn reply(&mut self, resp: &[u8]) -> bool {
+        let Some(buf) = self.response_buf().get_mut(..resp.len()) else {
+            return false;
+        };
+        buf.copy_from_slice(resp);
+        self.complete(resp.len());
+        true
+    }
+
+    /// Checker: post the command message of `cmd` with id `id`. Return
+    /// `false` if a command is still pending or the message does not fit.
+    pub fn send(&mut self, id: usize, cmd: &[u8]) -> bool {
+        let header = self.header();
+        let head = header.head.load(Ordering::Relaxed);
+        let id = crate::id_to_wire(id);
+        let len = id.len() + cmd.len();
+        if head != header.tail.load(Ordering::Acquire) || len > self.cmd_cap {
+            return false;
+        }
+        // SAFETY: `len <= cmd_cap` bytes of the command area, which the
+        // harness does not read while no command is pending.
+        let buf = unsafe { slice::from_raw_parts_mut(self.cmd, len) };
+        buf[..id.len()].copy_from_slice(&id);
+        buf[id.len()..].copy_from_slice(cmd);
+        let header = self.header();
+        header.cmd_len.store(len as u32, Ordering::Relaxed);
+        header.head.store(head.wrapping_add(1), Ordering::Release);
+        self.sent = true;
+        true
+    }
+
+    /// Checker: return the response to the latest command once the harness
+    /// has published it, or `None` if no command has been sent.
+    pub fn poll_response(&self) -> Option<&[u8]> {
+        let header = self.header();
+        if !self.sent || header.tail.load(Ordering::Acquire) != header.head.load(Ordering::Relaxed)
+        {
+            return None;
+        }
+        let len = (header.resp_len.load(Ordering::Relaxed) as usize).min(self.resp_cap);
+        // SAFETY: the response area holds `resp_cap` bytes of the region, and
+        // the harness does not write it until the next command is sent.
+        Some(unsafe { slice::from_raw_parts(self.resp, len) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exchange() {
+        let mut region = [0u32; 32];
+        let base = region.as_mut_ptr() as *mut u8;
+        let len = size_of_val(&region);
+        // SAFETY: both mailboxes share `region`, which outlives them.
+        let (mut checker, mut harness) = unsafe {
+            (
+                Mailbox::new(base, len, 32).unwrap(),
+                Mailbox::new(base, len, 32).unwrap(),
+            )
+        };
+        checker.reset();
+        assert_eq!(checker.poll_response(), None);
+        assert_eq!(harness.poll(), None);
+
+        assert!(checker.send(7, &[1, 2]));
+        assert!(!checker.send(7, &[3]));
+        assert_eq!(checker.poll_response(), None);
+        assert_eq!(harness.poll(), Some(&[7, 0, 0, 0, 1, 2][..]));
+
+        assert!(harness.reply(&[9, 8, 7]));
+        assert_eq!(harness.poll(), None);
+        assert_eq!(checker.poll_response(), Some(&[9, 8, 7][..]));
+
+        checker.reset();
+        assert_eq!(checker.poll_response(), None);
+    }
+
+    #[test]
+    fn bounds() {
+        let mut region = [0u32; 8];
+        let base = region.as_mut_ptr() as *mut u8;
+        let len = size_of_val(&region);
+        // SAFETY: `region` outlives the mailboxes.
+        unsafe {
+            assert!(Mailbox::new(base.add(1), len - 1, 0).is_none());
+            assert!(Mailbox::new(base, len, len).is_none());
+        }
+        // SAFETY: as above.
+        let mut mailbox = unsafe { Mailbox::new(base, len, 8).unwrap() };
+        mailbox.reset();
+        assert!(!mailbox.send(1, &[0; 5]));
+        assert!(mailbox.send(1, &[0; 4]));
+        assert!(!mailbox.reply(&[0; 9]));
+        assert!(mailbox.reply(&[0; 8]));
+    }
+}
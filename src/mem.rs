@@ -1,7 +1,14 @@
-use crate::command;
+use crate::{
+    command,
+    size::MaxSize,
+    validate::{Validate, ValidationError},
+};
 use bitflags::bitflags;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+/// Page size assumed when checking address alignment.
+pub const PAGE_SIZE: usize = 4096;
+
 command! {
     /// [`Brk`] and [`Sbrk] change the location of the program break,
     /// which defines the end of the process's data segment.
@@ -39,7 +46,14 @@ command! {
         prot: ProtFlags,
         /// Mapping flags
         flags: MmapFlags,
-        // File-related fields
+        /// The file to map, `-1` for anonymous mappings. Commands of version 1
+        /// decode with `-1`.
+        #[km(since = 2, default = -1)]
+        fd: isize,
+        /// Offset in the file of the mapping, page aligned. Zero for
+        /// anonymous mappings.
+        #[km(since = 2, default)]
+        offset: usize,
     },
     222
 }
@@ -75,10 +89,137 @@ command! {
     5
 }
 
+/// Check that `addr` is page aligned and `len` is not zero.
+fn check_range(addr: usize, len: usize) -> Result<(), ValidationError> {
+    if !addr.is_multiple_of(PAGE_SIZE) {
+        return Err(ValidationError::Unaligned);
+    }
+    if len == 0 {
+        return Err(ValidationError::ZeroLength);
+    }
+    Ok(())
+}
+
+impl Validate for Brk {}
+
+impl Validate for Sbrk {}
+
+impl Validate for Mmap {
+    fn validate(&self) -> Result<(), ValidationError> {
+        if self.len == 0 {
+            return Err(ValidationError::ZeroLength);
+        }
+        let sharing = self.flags & (MmapFlags::MAP_SHARED | MmapFlags::MAP_PRIVATE);
+        if sharing.bits().count_ones() != 1 {
+            return Err(ValidationError::ConflictingFlags);
+        }
+        let fixed = self
+            .flags
+            .intersects(MmapFlags::MAP_FIXED | MmapFlags::MAP_FIXED_NOREPLACE);
+        if fixed && !self.addr.is_multiple_of(PAGE_SIZE) {
+            return Err(ValidationError::Unaligned);
+        }
+        if !self.flags.contains(MmapFlags::MAP_ANONYMOUS) {
+            if self.fd < 0 {
+                return Err(ValidationError::BadFd);
+            }
+            if !self.offset.is_multiple_of(PAGE_SIZE) {
+                return Err(ValidationError::Unaligned);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Validate for Munmap {
+    fn validate(&self) -> Result<(), ValidationError> {
+        check_range(self.addr, self.len)
+    }
+}
+
+impl Validate for Mprotect {
+    fn validate(&self) -> Result<(), ValidationError> {
+        if !self.start.is_multiple_of(PAGE_SIZE) {
+            return Err(ValidationError::Unaligned);
+        }
+        Ok(())
+    }
+}
+
+/// Builder of [`Mmap`] commands. By default the mapping is private,
+/// anonymous, readable and writable, at an address chosen by the kernel.
+#[derive(Debug, Clone)]
+pub struct MmapBuilder {
+    cmd: Mmap,
+}
+
+impl Mmap {
+    /// Start building a command mapping `len` bytes.
+    pub fn builder(len: usize) -> MmapBuilder {
+        MmapBuilder {
+            cmd: Mmap::new(
+                0,
+                len,
+                ProtFlags::READ | ProtFlags::WRITE,
+                MmapFlags::MAP_PRIVATE | MmapFlags::MAP_ANONYMOUS,
+                -1,
+                0,
+            ),
+        }
+    }
+}
+
+impl MmapBuilder {
+    /// Use `addr` as a hint for the address of the mapping.
+    pub fn addr(mut self, addr: usize) -> Self {
+        self.cmd.addr = addr;
+        self
+    }
+
+    /// Place the mapping at exactly `addr`.
+    pub fn fixed(mut self, addr: usize) -> Self {
+        self.cmd.addr = addr;
+        self.cmd.flags.insert(MmapFlags::MAP_FIXED);
+        self
+    }
+
+    /// Set the memory protection of the mapping.
+    pub fn prot(mut self, prot: ProtFlags) -> Self {
+        self.cmd.prot = prot;
+        self
+    }
+
+    /// Share modifications with other mappings of the same region.
+    pub fn shared(mut self) -> Self {
+        self.cmd.flags.remove(MmapFlags::MAP_PRIVATE);
+        self.cmd.flags.insert(MmapFlags::MAP_SHARED);
+        self
+    }
+
+    /// Map the file `fd` starting at `offset` instead of anonymous memory.
+    pub fn file(mut self, fd: isize, offset: usize) -> Self {
+        self.cmd.flags.remove(MmapFlags::MAP_ANONYMOUS);
+        self.cmd.fd = fd;
+        self.cmd.offset = offset;
+        self
+    }
+
+    /// Add `flags` to the mapping flags.
+    pub fn flags(mut self, flags: MmapFlags) -> Self {
+        self.cmd.flags |= flags;
+        self
+    }
+
+    /// Build the command.
+    pub fn build(self) -> Mmap {
+        self.cmd
+    }
+}
+
 bitflags! {
     /// Generic page table entry flags that indicate the corresponding mapped
     /// memory region permissions and attributes.
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub struct ProtFlags: u8 {
         /// The memory is readable.
         const READ          = 1 << 0;
@@ -89,6 +230,10 @@ bitflags! {
     }
 }
 
+impl MaxSize for ProtFlags {
+    const MAX_SIZE: usize = u8::MAX_SIZE;
+}
+
 impl Serialize for ProtFlags {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -113,7 +258,10 @@ bitflags! {
     /// `MmapFlags` determines whether updates to the mapping are
     /// visible to other processes mapping the same region, and whether
     /// updates are carried through to the underlying file.
-    #[derive(Debug)]
+    ///
+    /// Values are those of Linux on x86 and most other architectures, so
+    /// the bits can be passed to `mmap` directly.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub struct MmapFlags: u32 {
         /// Modifications to this memory are shared
         const MAP_SHARED = 1 << 0;
@@ -124,9 +272,24 @@ bitflags! {
         const MAP_FIXED = 1 << 4;
         /// No backend file is associated with the mapping.
         const MAP_ANONYMOUS = 1 << 5;
+        /// Do not reserve swap space for the mapping.
+        const MAP_NORESERVE = 1 << 14;
+        /// Prefault the page tables of the mapping.
+        const MAP_POPULATE = 1 << 15;
+        /// Allocate the mapping at an address suitable for a stack.
+        const MAP_STACK = 1 << 17;
+        /// Allocate the mapping using huge pages.
+        const MAP_HUGETLB = 1 << 18;
+        /// Like `MAP_FIXED`, but fail with `EEXIST` instead of replacing an
+        /// existing mapping.
+        const MAP_FIXED_NOREPLACE = 1 << 20;
     }
 }
 
+impl MaxSize for MmapFlags {
+    const MAX_SIZE: usize = u32::MAX_SIZE;
+}
+
 impl Serialize for MmapFlags {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -146,3 +309,84 @@ impl<'de> Deserialize<'de> for MmapFlags {
         )?))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mmap_builder() {
+        let anon = Mmap::builder(PAGE_SIZE).build();
+        assert_eq!((anon.fd, anon.offset), (-1, 0));
+        assert_eq!(anon.validate(), Ok(()));
+        let file = Mmap::builder(PAGE_SIZE).file(3, PAGE_SIZE).build();
+        assert!(!file.flags.contains(MmapFlags::MAP_ANONYMOUS));
+        assert_eq!(file.validate(), Ok(()));
+        let bad = Mmap::builder(PAGE_SIZE).file(-1, 0).build();
+        assert_eq!(bad.validate(), Err(ValidationError::BadFd));
+    }
+
+    #[cfg(feature = "postcard")]
+    #[test]
+    fn mmap_anonymous_trims_file_fields() {
+        let anon = Mmap::builder(PAGE_SIZE).build();
+        assert_eq!(anon.trailing_default_size(), 2);
+        let file = Mmap::builder(PAGE_SIZE).file(3, 0).build();
+        assert_eq!(file.trailing_default_size(), 1);
+    }
+
+    #[cfg(feature = "postcard")]
+    fn file_mmap() -> Mmap {
+        Mmap::builder(2 * PAGE_SIZE).file(5, PAGE_SIZE).build()
+    }
+
+    #[cfg(feature = "postcard")]
+    #[test]
+    fn mmap_decode_v1() {
+        let cmd = file_mmap();
+        // A version 1 payload ends before `fd` and `offset`.
+        let trim = crate::__serialized_size(&cmd.fd) + crate::__serialized_size(&cmd.offset);
+        let mut buf = [0; 32];
+        let bytes = crate::encode_versioned_to_slice(1, &cmd, trim, &mut buf).unwrap();
+        let (decoded, rest) = Mmap::decode_versioned(bytes).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!((decoded.addr, decoded.len), (cmd.addr, cmd.len));
+        assert_eq!((decoded.prot, decoded.flags), (cmd.prot, cmd.flags));
+        assert_eq!((decoded.fd, decoded.offset), (-1, 0));
+    }
+
+    #[cfg(feature = "postcard")]
+    #[test]
+    fn mmap_decode_newer() {
+        let cmd = file_mmap();
+        // A later version appends a field, which older decoders skip.
+        let newer = (cmd.clone(), 0x1234_u32);
+        let mut buf = [0; 32];
+        let len = crate::encode_versioned_to_slice(Mmap::VERSION + 1, &newer, 0, &mut buf)
+            .unwrap()
+            .len();
+        buf[len] = 0xaa;
+        let (decoded, rest) = Mmap::decode_versioned(&buf[..len + 1]).unwrap();
+        assert_eq!(decoded, cmd);
+        assert_eq!(rest, [0xaa]);
+    }
+
+    #[cfg(feature = "postcard")]
+    #[test]
+    fn mmap_decode_truncated() {
+        let cmd = file_mmap();
+        let mut buf = [0; 32];
+        let bytes = crate::encode_versioned_to_slice(Mmap::VERSION, &cmd, 0, &mut buf).unwrap();
+        for len in 0..bytes.len() {
+            assert!(
+                Mmap::decode_versioned(&bytes[..len]).is_none(),
+                "{} bytes",
+                len
+            );
+        }
+        // The length prefix is consistent, but the payload ends inside `addr`.
+        assert!(Mmap::decode_versioned(&[Mmap::VERSION, 1, 0x80]).is_none());
+        // Version 0 does not exist.
+        assert!(Mmap::decode_versioned(&[0, 0]).is_none());
+    }
+}
@@ -0,0 +1,21 @@
+//! Reference models execute commands in memory to produce the results
+//! expected from the kernel under test, so that the checker can diff the
+//! two instead of hard-coding expectations.
+
+use crate::error::LinuxError;
+
+/// In-memory filesystem model.
+pub mod fs;
+
+/// In-memory virtual memory model.
+pub mod mem;
+
+/// A model that executes commands of type `C`.
+pub trait Execute<C> {
+    /// Result of a successful command: its response type, or `()` for
+    /// commands without a typed response.
+    type Output;
+
+    /// Execute `cmd`, return the expected result or the expected error.
+    fn execute(&mut self, cmd: &C) -> Result<Self::Output, LinuxError>;
+}
@@ -0,0 +1,892 @@
+use super::Execute;
+use crate::{
+    error::LinuxError,
+    fs::{
+        Chdir, Close, Dirent, DirentWriter, Dup, FdResult, FileKind, FileMode, Fstat, FstatResult,
+        Getcwd, GetcwdResult, Getdents, Getdents1, GetdentsResult, LibcStat, Linkat, Mkdirat,
+        OpenFlags, Openat, Path, Unlinkat, UnlinkatFlags, AT_FDCWD, MAX_NAME_LEN,
+    },
+};
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+/// Inode number of the root directory.
+pub const ROOT_INO: u64 = 1;
+
+/// Default limit on the number of open file descriptors.
+pub const DEFAULT_MAX_FDS: usize = 1024;
+
+/// Default file mode creation mask.
+pub const DEFAULT_UMASK: u32 = 0o022;
+
+/// Block size reported by `fstat`.
+const BLKSIZE: u32 = 4096;
+
+/// Permission bits, shifted to the owner, group or other position.
+const MAY_READ: u32 = 0o4;
+const MAY_WRITE: u32 = 0o2;
+const MAY_EXEC: u32 = 0o1;
+
+/// A file of the model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inode {
+    /// File kind.
+    pub kind: FileKind,
+    /// Permission bits.
+    pub mode: FileMode,
+    /// Owner user id.
+    pub uid: u32,
+    /// Owner group id.
+    pub gid: u32,
+    /// Number of hard links, counting `.` and the entries of subdirectories
+    /// for directories.
+    pub nlink: u32,
+    /// Parent directory, for directories.
+    pub parent: u64,
+    /// Entries by name, excluding `.` and `..`, for directories.
+    pub entries: BTreeMap<String, u64>,
+}
+
+/// An open file description, shared by duplicated file descriptors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct OpenFile {
+    ino: u64,
+    flags: OpenFlags,
+    /// Index of the next directory entry returned by `getdents`.
+    pos: usize,
+    /// Number of file descriptors referring to the description.
+    refs: usize,
+}
+
+/// In-memory filesystem of a single process, with inodes, a file descriptor
+/// table, a working directory and permission checks.
+///
+/// File contents are not modelled, so regular files are always empty.
+/// File descriptors 0, 1 and 2 are open on a console device, like for a
+/// freshly started process. Directory entries are listed as `.`, `..`, then
+/// the other entries sorted by name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FsModel {
+    inodes: BTreeMap<u64, Inode>,
+    next_ino: u64,
+    files: BTreeMap<u64, OpenFile>,
+    next_file: u64,
+    fds: BTreeMap<isize, u64>,
+    max_fds: usize,
+    cwd: u64,
+    umask: u32,
+    uid: u32,
+    gid: u32,
+}
+
+impl Default for FsModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FsModel {
+    /// Create a model with an empty root directory, run by root.
+    pub fn new() -> Self {
+        let mut model = Self {
+            inodes: BTreeMap::new(),
+            next_ino: ROOT_INO,
+            files: BTreeMap::new(),
+            next_file: 0,
+            fds: BTreeMap::new(),
+            max_fds: DEFAULT_MAX_FDS,
+            cwd: ROOT_INO,
+            umask: DEFAULT_UMASK,
+            uid: 0,
+            gid: 0,
+        };
+        let root = model.alloc_inode(FileKind::Directory, FileMode::from_bits_truncate(0o755));
+        model.inodes.get_mut(&root).unwrap().nlink = 2;
+        let console = model.alloc_inode(
+            FileKind::CharDevice,
+            FileMode::USER_READ | FileMode::USER_WRITE | FileMode::GROUP_WRITE,
+        );
+        for fd in 0..3 {
+            model.install_fd(fd, console, OpenFlags::RDWR);
+        }
+        model
+    }
+
+    /// Run as user `uid` and group `gid`. Permission checks are skipped for
+    /// root, i.e. user 0.
+    pub fn with_credentials(mut self, uid: u32, gid: u32) -> Self {
+        self.uid = uid;
+        self.gid = gid;
+        self
+    }
+
+    /// Set the file mode creation mask.
+    pub fn with_umask(mut self, umask: u32) -> Self {
+        self.umask = umask & 0o777;
+        self
+    }
+
+    /// Set the limit on the number of open file descriptors.
+    pub fn with_max_fds(mut self, max_fds: usize) -> Self {
+        self.max_fds = max_fds;
+        self
+    }
+
+    /// Inode `ino`, if it exists.
+    pub fn inode(&self, ino: u64) -> Option<&Inode> {
+        self.inodes.get(&ino)
+    }
+
+    /// Inode of the current working directory.
+    pub fn cwd(&self) -> u64 {
+        self.cwd
+    }
+
+    /// Inode open as `fd`, if any.
+    pub fn fd(&self, fd: isize) -> Option<u64> {
+        self.file(fd).ok().map(|file| file.ino)
+    }
+
+    /// Open file descriptors in ascending order.
+    pub fn fds(&self) -> impl Iterator<Item = isize> + '_ {
+        self.fds.keys().copied()
+    }
+
+    /// Inode at `path`, resolved relative to `dirfd`.
+    pub fn resolve(&self, dirfd: isize, path: &str) -> Result<u64, LinuxError> {
+        let (parent, name, dir_only) = self.lookup_parent(dirfd, path)?;
+        let ino = match name {
+            Some(name) => self.child(parent, name)?.ok_or(LinuxError::ENOENT)?,
+            None => parent,
+        };
+        if dir_only && self.inodes[&ino].kind != FileKind::Directory {
+            return Err(LinuxError::ENOTDIR);
+        }
+        Ok(ino)
+    }
+
+    /// Status of inode `ino`, with times and device numbers zeroed.
+    pub fn stat(&self, ino: u64) -> Option<LibcStat> {
+        let inode = self.inodes.get(&ino)?;
+        let mut stat = LibcStat::default();
+        stat.ino = ino;
+        stat.mode = inode.kind.mode_bits() | inode.mode.bits();
+        stat.nlink = inode.nlink;
+        stat.uid = inode.uid;
+        stat.gid = inode.gid;
+        stat.blksize = BLKSIZE;
+        Some(stat)
+    }
+
+    /// Directory listing of inode `ino`, in the order returned by `getdents`.
+    pub fn list(&self, ino: u64) -> Option<Vec<Dirent>> {
+        let inode = self.inodes.get(&ino)?;
+        if inode.kind != FileKind::Directory {
+            return None;
+        }
+        let mut entries = Vec::with_capacity(inode.entries.len() + 2);
+        if inode.nlink > 0 {
+            entries.push(self.dirent(ino, "."));
+            entries.push(self.dirent(inode.parent, ".."));
+        }
+        for (name, &child) in &inode.entries {
+            entries.push(self.dirent(child, name));
+        }
+        Some(entries)
+    }
+
+    fn dirent(&self, ino: u64, name: &str) -> Dirent {
+        Dirent {
+            ino,
+            kind: self.inodes[&ino].kind,
+            name: name.try_into().expect("model names fit in a dirent"),
+        }
+    }
+
+    fn alloc_inode(&mut self, kind: FileKind, mode: FileMode) -> u64 {
+        let ino = self.next_ino;
+        self.next_ino += 1;
+        self.inodes.insert(
+            ino,
+            Inode {
+                kind,
+                mode,
+                uid: self.uid,
+                gid: self.gid,
+                nlink: 0,
+                parent: ino,
+                entries: BTreeMap::new(),
+            },
+        );
+        ino
+    }
+
+    /// Free inode `ino` if it is neither linked nor in use.
+    fn release(&mut self, ino: u64) {
+        let unlinked = self.inodes.get(&ino).is_some_and(|inode| inode.nlink == 0);
+        let in_use = self.cwd == ino || self.files.values().any(|file| file.ino == ino);
+        if unlinked && !in_use {
+            self.inodes.remove(&ino);
+        }
+    }
+
+    fn install_fd(&mut self, fd: isize, ino: u64, flags: OpenFlags) {
+        let id = self.next_file;
+        self.next_file += 1;
+        let file = OpenFile {
+            ino,
+            flags,
+            pos: 0,
+            refs: 1,
+        };
+        self.files.insert(id, file);
+        self.fds.insert(fd, id);
+    }
+
+    /// Lowest unused file descriptor.
+    fn free_fd(&self) -> Result<isize, LinuxError> {
+        let fd = (0..)
+            .zip(self.fds.keys())
+            .find(|(expected, &fd)| *expected != fd)
+            .map_or(self.fds.len() as isize, |(fd, _)| fd);
+        if fd as usize >= self.max_fds {
+            return Err(LinuxError::EMFILE);
+        }
+        Ok(fd)
+    }
+
+    fn file(&self, fd: isize) -> Result<&OpenFile, LinuxError> {
+        let id = self.fds.get(&fd).ok_or(LinuxError::EBADF)?;
+        Ok(&self.files[id])
+    }
+
+    /// Check if the current user may access inode `ino` as `access`, a
+    /// combination of `MAY_*` bits.
+    fn may(&self, ino: u64, access: u32) -> bool {
+        let inode = &self.inodes[&ino];
+        if self.uid == 0 {
+            return true;
+        }
+        let mode = inode.mode.bits();
+        let bits = if inode.uid == self.uid {
+            mode >> 6
+        } else if inode.gid == self.gid {
+            mode >> 3
+        } else {
+            mode
+        };
+        bits & access == access
+    }
+
+    /// Entry `name` of directory `dir`, handling `.` and `..`.
+    ///
+    /// Lookups other than `.` in a removed directory fail with `ENOENT`, as
+    /// its parent may be gone already.
+    fn child(&self, dir: u64, name: &str) -> Result<Option<u64>, LinuxError> {
+        if name.len() > MAX_NAME_LEN {
+            return Err(LinuxError::ENAMETOOLONG);
+        }
+        let inode = &self.inodes[&dir];
+        if inode.nlink == 0 && name != "." {
+            return Err(LinuxError::ENOENT);
+        }
+        Ok(match name {
+            "." => Some(dir),
+            ".." => Some(inode.parent),
+            _ => inode.entries.get(name).copied(),
+        })
+    }
+
+    /// Resolve all but the last component of `path` relative to `dirfd`.
+    ///
+    /// Return the parent directory, the last component, or `None` if `path`
+    /// is the root, and whether `path` ends with a slash so that it must
+    /// name a directory.
+    fn lookup_parent<'p>(
+        &self,
+        dirfd: isize,
+        path: &'p str,
+    ) -> Result<(u64, Option<&'p str>, bool), LinuxError> {
+        if path.is_empty() {
+            return Err(LinuxError::ENOENT);
+        }
+        let mut dir = if path.starts_with('/') {
+            ROOT_INO
+        } else if dirfd == AT_FDCWD {
+            self.cwd
+        } else {
+            let ino = self.file(dirfd)?.ino;
+            if self.inodes[&ino].kind != FileKind::Directory {
+                return Err(LinuxError::ENOTDIR);
+            }
+            ino
+        };
+        let mut components = path.split('/').filter(|name| !name.is_empty());
+        let mut last = components.next();
+        for next in components {
+            let name = last.replace(next).unwrap();
+            if !self.may(dir, MAY_EXEC) {
+                return Err(LinuxError::EACCES);
+            }
+            dir = self.child(dir, name)?.ok_or(LinuxError::ENOENT)?;
+            if self.inodes[&dir].kind != FileKind::Directory {
+                return Err(LinuxError::ENOTDIR);
+            }
+        }
+        if last.is_some() && !self.may(dir, MAY_EXEC) {
+            return Err(LinuxError::EACCES);
+        }
+        Ok((dir, last, path.ends_with('/')))
+    }
+
+    /// Check that a new entry can be added to directory `dir`.
+    fn may_create(&self, dir: u64) -> Result<(), LinuxError> {
+        if self.inodes[&dir].nlink == 0 {
+            return Err(LinuxError::ENOENT);
+        }
+        if !self.may(dir, MAY_WRITE | MAY_EXEC) {
+            return Err(LinuxError::EACCES);
+        }
+        Ok(())
+    }
+
+    fn link(&mut self, dir: u64, name: &str, ino: u64) {
+        self.inodes
+            .get_mut(&dir)
+            .unwrap()
+            .entries
+            .insert(name.to_string(), ino);
+        self.inodes.get_mut(&ino).unwrap().nlink += 1;
+    }
+
+    fn openat(&mut self, cmd: &Openat) -> Result<FdResult, LinuxError> {
+        let fd = self.free_fd()?;
+        let (parent, name, dir_only) = self.lookup_parent(cmd.dirfd, &cmd.path)?;
+        let existing = match name {
+            Some(name) => self.child(parent, name)?,
+            None => Some(parent),
+        };
+        let access = cmd.flags.bits() & 0o3;
+        let ino = match existing {
+            Some(ino) => {
+                let kind = self.inodes[&ino].kind;
+                if dir_only && kind != FileKind::Directory {
+                    return Err(LinuxError::ENOTDIR);
+                }
+                if kind == FileKind::Directory {
+                    if access != 0 || cmd.flags.contains(OpenFlags::CREAT) {
+                        return Err(LinuxError::EISDIR);
+                    }
+                } else if cmd.flags.contains(OpenFlags::DIRECTORY) {
+                    return Err(LinuxError::ENOTDIR);
+                }
+                let want = match access {
+                    0 => MAY_READ,
+                    1 => MAY_WRITE,
+                    _ => MAY_READ | MAY_WRITE,
+                };
+                if !self.may(ino, want) {
+                    return Err(LinuxError::EACCES);
+                }
+                ino
+            }
+            None if cmd.flags.contains(OpenFlags::CREAT) => {
+                if dir_only {
+                    return Err(LinuxError::EISDIR);
+                }
+                self.may_create(parent)?;
+                let mode = FileMode::from_bits_truncate(cmd.mode.bits() & !self.umask);
+                let ino = self.alloc_inode(FileKind::File, mode);
+                self.link(parent, name.unwrap(), ino);
+                ino
+            }
+            None => return Err(LinuxError::ENOENT),
+        };
+        self.install_fd(fd, ino, cmd.flags);
+        Ok(FdResult::new(fd))
+    }
+
+    fn getdents(&mut self, fd: isize, len: usize) -> Result<GetdentsResult, LinuxError> {
+        let id = *self.fds.get(&fd).ok_or(LinuxError::EBADF)?;
+        let file = &self.files[&id];
+        let entries = self.list(file.ino).ok_or(LinuxError::ENOTDIR)?;
+        let mut result = GetdentsResult::new(heapless::Vec::new());
+        let mut used = 0;
+        for entry in entries.into_iter().skip(file.pos) {
+            used += DirentWriter::reclen(entry.name.len());
+            if used > len {
+                if result.entries.is_empty() {
+                    return Err(LinuxError::EINVAL);
+                }
+                break;
+            }
+            if result.entries.push(entry).is_err() {
+                break;
+            }
+        }
+        self.files.get_mut(&id).unwrap().pos += result.entries.len();
+        Ok(result)
+    }
+
+    fn unlinkat(&mut self, cmd: &Unlinkat) -> Result<(), LinuxError> {
+        let rmdir = cmd.flags.contains(UnlinkatFlags::REMOVEDIR);
+        let (parent, name, dir_only) = self.lookup_parent(cmd.dirfd, &cmd.path)?;
+        let name = match (name, rmdir) {
+            (None, true) => return Err(LinuxError::EBUSY),
+            (Some("."), true) => return Err(LinuxError::EINVAL),
+            (Some(".."), true) => return Err(LinuxError::ENOTEMPTY),
+            (None | Some(".") | Some(".."), false) => return Err(LinuxError::EISDIR),
+            (Some(name), _) => name,
+        };
+        let ino = self.child(parent, name)?.ok_or(LinuxError::ENOENT)?;
+        if !self.may(parent, MAY_WRITE | MAY_EXEC) {
+            return Err(LinuxError::EACCES);
+        }
+        let inode = &self.inodes[&ino];
+        let is_dir = inode.kind == FileKind::Directory;
+        if rmdir || dir_only {
+            if !is_dir {
+                return Err(LinuxError::ENOTDIR);
+            }
+            if !rmdir {
+                return Err(LinuxError::EISDIR);
+            }
+            if !inode.entries.is_empty() {
+                return Err(LinuxError::ENOTEMPTY);
+            }
+        } else if is_dir {
+            return Err(LinuxError::EISDIR);
+        }
+        self.inodes.get_mut(&parent).unwrap().entries.remove(name);
+        if is_dir {
+            self.inodes.get_mut(&parent).unwrap().nlink -= 1;
+            self.inodes.get_mut(&ino).unwrap().nlink = 0;
+        } else {
+            self.inodes.get_mut(&ino).unwrap().nlink -= 1;
+        }
+        self.release(ino);
+        Ok(())
+    }
+
+    fn getcwd(&self) -> Result<GetcwdResult, LinuxError> {
+        if self.inodes[&self.cwd].nlink == 0 {
+            return Err(LinuxError::ENOENT);
+        }
+        let mut names = Vec::new();
+        let mut ino = self.cwd;
+        while ino != ROOT_INO {
+            let parent = self.inodes[&ino].parent;
+            let (name, _) = self.inodes[&parent]
+                .entries
+                .iter()
+                .find(|(_, &child)| child == ino)
+                .expect("linked directory is listed in its parent");
+            names.push(name.as_str());
+            ino = parent;
+        }
+        let mut path = String::new();
+        for name in names.iter().rev() {
+            path.push('/');
+            path.push_str(name);
+        }
+        if path.is_empty() {
+            path.push('/');
+        }
+        let path = Path::try_from(path.as_str()).map_err(|_| LinuxError::ERANGE)?;
+        Ok(GetcwdResult::new(path))
+    }
+}
+
+impl Execute<Openat> for FsModel {
+    type Output = FdResult;
+
+    fn execute(&mut self, cmd: &Openat) -> Result<FdResult, LinuxError> {
+        self.openat(cmd)
+    }
+}
+
+impl Execute<Close> for FsModel {
+    type Output = ();
+
+    fn execute(&mut self, cmd: &Close) -> Result<(), LinuxError> {
+        let id = self.fds.remove(&cmd.fd).ok_or(LinuxError::EBADF)?;
+        let file = self.files.get_mut(&id).unwrap();
+        file.refs -= 1;
+        if file.refs == 0 {
+            let ino = file.ino;
+            self.files.remove(&id);
+            self.release(ino);
+        }
+        Ok(())
+    }
+}
+
+impl Execute<Fstat> for FsModel {
+    type Output = FstatResult;
+
+    fn execute(&mut self, cmd: &Fstat) -> Result<FstatResult, LinuxError> {
+        let ino = self.file(cmd.fd)?.ino;
+        Ok(FstatResult::new(self.stat(ino).unwrap()))
+    }
+}
+
+impl Execute<Getdents> for FsModel {
+    type Output = GetdentsResult;
+
+    fn execute(&mut self, cmd: &Getdents) -> Result<GetdentsResult, LinuxError> {
+        self.getdents(cmd.fd, cmd.len)
+    }
+}
+
+impl Execute<Getdents1> for FsModel {
+    type Output = GetdentsResult;
+
+    fn execute(&mut self, cmd: &Getdents1) -> Result<GetdentsResult, LinuxError> {
+        let mut result = self.getdents(cmd.fd, usize::MAX)?;
+        // Only the first entry fits in the buffer; rewind past the others.
+        if result.entries.len() > 1 {
+            let id = self.fds[&cmd.fd];
+            self.files.get_mut(&id).unwrap().pos -= result.entries.len() - 1;
+            result.entries.truncate(1);
+        }
+        Ok(result)
+    }
+}
+
+impl Execute<Linkat> for FsModel {
+    type Output = ();
+
+    fn execute(&mut self, cmd: &Linkat) -> Result<(), LinuxError> {
+        let ino = self.resolve(cmd.olddirfd, &cmd.oldpath)?;
+        let (parent, name, dir_only) = self.lookup_parent(cmd.newdirfd, &cmd.newpath)?;
+        let name = match name {
+            Some(name) if self.child(parent, name)?.is_none() => name,
+            _ => return Err(LinuxError::EEXIST),
+        };
+        if dir_only {
+            return Err(LinuxError::ENOENT);
+        }
+        self.may_create(parent)?;
+        if self.inodes[&ino].kind == FileKind::Directory {
+            return Err(LinuxError::EPERM);
+        }
+        self.link(parent, name, ino);
+        Ok(())
+    }
+}
+
+impl Execute<Unlinkat> for FsModel {
+    type Output = ();
+
+    fn execute(&mut self, cmd: &Unlinkat) -> Result<(), LinuxError> {
+        self.unlinkat(cmd)
+    }
+}
+
+impl Execute<Mkdirat> for FsModel {
+    type Output = ();
+
+    fn execute(&mut self, cmd: &Mkdirat) -> Result<(), LinuxError> {
+        let (parent, name, _) = self.lookup_parent(cmd.dirfd, &cmd.path)?;
+        let name = match name {
+            Some(name) if self.child(parent, name)?.is_none() => name,
+            _ => return Err(LinuxError::EEXIST),
+        };
+        self.may_create(parent)?;
+        let mode = FileMode::from_bits_truncate(cmd.mode.bits() & !self.umask);
+        let ino = self.alloc_inode(FileKind::Directory, mode);
+        self.link(parent, name, ino);
+        let inode = self.inodes.get_mut(&ino).unwrap();
+        inode.parent = parent;
+        inode.nlink += 1;
+        self.inodes.get_mut(&parent).unwrap().nlink += 1;
+        Ok(())
+    }
+}
+
+impl Execute<Getcwd> for FsModel {
+    type Output = GetcwdResult;
+
+    fn execute(&mut self, _cmd: &Getcwd) -> Result<GetcwdResult, LinuxError> {
+        self.getcwd()
+    }
+}
+
+impl Execute<Dup> for FsModel {
+    type Output = FdResult;
+
+    fn execute(&mut self, cmd: &Dup) -> Result<FdResult, LinuxError> {
+        let id = *self.fds.get(&cmd.oldfd).ok_or(LinuxError::EBADF)?;
+        let fd = self.free_fd()?;
+        self.files.get_mut(&id).unwrap().refs += 1;
+        self.fds.insert(fd, id);
+        Ok(FdResult::new(fd))
+    }
+}
+
+impl Execute<Chdir> for FsModel {
+    type Output = ();
+
+    fn execute(&mut self, cmd: &Chdir) -> Result<(), LinuxError> {
+        let ino = self.resolve(AT_FDCWD, &cmd.path)?;
+        if self.inodes[&ino].kind != FileKind::Directory {
+            return Err(LinuxError::ENOTDIR);
+        }
+        if !self.may(ino, MAY_EXEC) {
+            return Err(LinuxError::EACCES);
+        }
+        let old = core::mem::replace(&mut self.cwd, ino);
+        self.release(old);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(s: &str) -> Path {
+        Path::try_from(s).unwrap()
+    }
+
+    fn mkdir(model: &mut FsModel, p: &str, mode: u32) -> Result<(), LinuxError> {
+        model.execute(&Mkdirat::new(
+            AT_FDCWD,
+            path(p),
+            FileMode::from_bits_truncate(mode),
+        ))
+    }
+
+    fn open(model: &mut FsModel, p: &str, flags: OpenFlags) -> Result<isize, LinuxError> {
+        let mode = FileMode::from_bits_truncate(0o666);
+        let cmd = Openat::new(AT_FDCWD, path(p), flags, mode);
+        model.execute(&cmd).map(|result| result.fd)
+    }
+
+    fn unlink(model: &mut FsModel, p: &str, flags: UnlinkatFlags) -> Result<(), LinuxError> {
+        model.execute(&Unlinkat::new(AT_FDCWD, path(p), flags))
+    }
+
+    fn names(result: &GetdentsResult) -> Vec<&str> {
+        result
+            .entries
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn create() {
+        let mut model = FsModel::new();
+        assert_eq!(
+            open(&mut model, "f", OpenFlags::RDONLY),
+            Err(LinuxError::ENOENT)
+        );
+        let fd = open(&mut model, "f", OpenFlags::CREAT | OpenFlags::WRONLY).unwrap();
+        assert_eq!(fd, 3);
+        let stat = model.execute(&Fstat::new(fd)).unwrap().stat;
+        assert_eq!(stat.kind(), FileKind::File);
+        assert_eq!(stat.mode().bits(), 0o644);
+        assert_eq!(stat.nlink, 1);
+        assert_eq!(
+            open(&mut model, "f/", OpenFlags::RDONLY),
+            Err(LinuxError::ENOTDIR)
+        );
+        assert_eq!(
+            open(&mut model, "f", OpenFlags::DIRECTORY),
+            Err(LinuxError::ENOTDIR)
+        );
+        assert_eq!(
+            open(&mut model, "g/", OpenFlags::CREAT),
+            Err(LinuxError::EISDIR)
+        );
+
+        mkdir(&mut model, "d", 0o777).unwrap();
+        assert_eq!(mkdir(&mut model, "d", 0o777), Err(LinuxError::EEXIST));
+        assert_eq!(mkdir(&mut model, "/", 0o777), Err(LinuxError::EEXIST));
+        assert_eq!(mkdir(&mut model, "f/d", 0o777), Err(LinuxError::ENOTDIR));
+        assert_eq!(
+            open(&mut model, "d", OpenFlags::RDWR),
+            Err(LinuxError::EISDIR)
+        );
+        assert_eq!(model.stat(ROOT_INO).unwrap().nlink, 3);
+        let d = model.resolve(AT_FDCWD, "/d/.").unwrap();
+        assert_eq!(model.stat(d).unwrap().mode().bits(), 0o755);
+        assert_eq!(model.resolve(AT_FDCWD, "d/.."), Ok(ROOT_INO));
+        assert_eq!(model.resolve(AT_FDCWD, "/.."), Ok(ROOT_INO));
+    }
+
+    #[test]
+    fn unlink_and_link() {
+        let mut model = FsModel::new();
+        let fd = open(&mut model, "f", OpenFlags::CREAT).unwrap();
+        let link = Linkat::new(AT_FDCWD, path("f"), AT_FDCWD, path("g"));
+        model.execute(&link).unwrap();
+        assert_eq!(model.execute(&link), Err(LinuxError::EEXIST));
+        assert_eq!(model.resolve(AT_FDCWD, "f"), model.resolve(AT_FDCWD, "g"));
+        assert_eq!(model.execute(&Fstat::new(fd)).unwrap().stat.nlink, 2);
+
+        unlink(&mut model, "f", UnlinkatFlags::empty()).unwrap();
+        unlink(&mut model, "g", UnlinkatFlags::empty()).unwrap();
+        assert_eq!(
+            unlink(&mut model, "g", UnlinkatFlags::empty()),
+            Err(LinuxError::ENOENT)
+        );
+        // The open file outlives its last link.
+        let ino = model.fd(fd).unwrap();
+        assert_eq!(model.execute(&Fstat::new(fd)).unwrap().stat.nlink, 0);
+        model.execute(&Close::new(fd)).unwrap();
+        assert!(model.inode(ino).is_none());
+        assert_eq!(model.execute(&Close::new(fd)), Err(LinuxError::EBADF));
+
+        mkdir(&mut model, "d", 0o755).unwrap();
+        let link = Linkat::new(AT_FDCWD, path("d"), AT_FDCWD, path("e"));
+        assert_eq!(model.execute(&link), Err(LinuxError::EPERM));
+        assert_eq!(
+            unlink(&mut model, "d", UnlinkatFlags::empty()),
+            Err(LinuxError::EISDIR)
+        );
+        open(&mut model, "f", OpenFlags::CREAT).unwrap();
+        assert_eq!(
+            unlink(&mut model, "f", UnlinkatFlags::REMOVEDIR),
+            Err(LinuxError::ENOTDIR)
+        );
+    }
+
+    #[test]
+    fn rmdir_busy() {
+        let mut model = FsModel::new();
+        mkdir(&mut model, "/a", 0o755).unwrap();
+        mkdir(&mut model, "/a/b", 0o755).unwrap();
+        assert_eq!(
+            unlink(&mut model, "/a", UnlinkatFlags::REMOVEDIR),
+            Err(LinuxError::ENOTEMPTY)
+        );
+        assert_eq!(
+            unlink(&mut model, "/", UnlinkatFlags::REMOVEDIR),
+            Err(LinuxError::EBUSY)
+        );
+        assert_eq!(
+            unlink(&mut model, "/a/.", UnlinkatFlags::REMOVEDIR),
+            Err(LinuxError::EINVAL)
+        );
+        assert_eq!(
+            unlink(&mut model, "/a/b/..", UnlinkatFlags::REMOVEDIR),
+            Err(LinuxError::ENOTEMPTY)
+        );
+
+        model.execute(&Chdir::new(path("/a/b"))).unwrap();
+        assert_eq!(model.execute(&Getcwd::new()).unwrap().path.as_str(), "/a/b");
+        unlink(&mut model, "/a/b", UnlinkatFlags::REMOVEDIR).unwrap();
+        unlink(&mut model, "/a", UnlinkatFlags::REMOVEDIR).unwrap();
+        assert_eq!(model.execute(&Getcwd::new()), Err(LinuxError::ENOENT));
+        assert_eq!(
+            open(&mut model, "../x", OpenFlags::RDONLY),
+            Err(LinuxError::ENOENT)
+        );
+        assert_eq!(
+            open(&mut model, "x", OpenFlags::CREAT),
+            Err(LinuxError::ENOENT)
+        );
+        assert_eq!(mkdir(&mut model, "c", 0o755), Err(LinuxError::ENOENT));
+        let fd = open(&mut model, ".", OpenFlags::RDONLY).unwrap();
+        let listing = model.execute(&Getdents::new(fd, 1024)).unwrap();
+        assert!(listing.entries.is_empty());
+
+        model.execute(&Chdir::new(path("/"))).unwrap();
+        assert_eq!(model.execute(&Getcwd::new()).unwrap().path.as_str(), "/");
+    }
+
+    #[test]
+    fn getdents_paging() {
+        let mut model = FsModel::new();
+        mkdir(&mut model, "d", 0o755).unwrap();
+        for name in ["d/c", "d/a", "d/b"] {
+            open(&mut model, name, OpenFlags::CREAT).unwrap();
+        }
+        let fd = open(&mut model, "d", OpenFlags::DIRECTORY).unwrap();
+        let file = open(&mut model, "d/a", OpenFlags::RDONLY).unwrap();
+        assert_eq!(
+            model.execute(&Getdents::new(file, 1024)),
+            Err(LinuxError::ENOTDIR)
+        );
+        assert_eq!(
+            model.execute(&Getdents::new(42, 1024)),
+            Err(LinuxError::EBADF)
+        );
+
+        let one = DirentWriter::reclen(1);
+        assert_eq!(
+            model.execute(&Getdents::new(fd, one - 1)),
+            Err(LinuxError::EINVAL)
+        );
+        let first = model.execute(&Getdents::new(fd, 2 * one)).unwrap();
+        assert_eq!(names(&first), [".", ".."]);
+        // A duplicated descriptor shares the position.
+        let dup = model.execute(&Dup::new(fd)).unwrap().fd;
+        let second = model.execute(&Getdents1::new(dup)).unwrap();
+        assert_eq!(names(&second), ["a"]);
+        let rest = model.execute(&Getdents::new(fd, 1024)).unwrap();
+        assert_eq!(names(&rest), ["b", "c"]);
+        let end = model.execute(&Getdents::new(fd, 1024)).unwrap();
+        assert!(end.entries.is_empty());
+    }
+
+    #[test]
+    fn permissions() {
+        let mut model = FsModel::new().with_umask(0);
+        mkdir(&mut model, "/home", 0o777).unwrap();
+        model.execute(&Chdir::new(path("/home"))).unwrap();
+        let mut model = model.with_credentials(1000, 1000);
+        assert_eq!(mkdir(&mut model, "/d", 0o755), Err(LinuxError::EACCES));
+        mkdir(&mut model, "ro", 0o555).unwrap();
+        mkdir(&mut model, "noexec", 0o666).unwrap();
+        assert_eq!(
+            open(&mut model, "ro/f", OpenFlags::CREAT),
+            Err(LinuxError::EACCES)
+        );
+        assert_eq!(mkdir(&mut model, "ro/d", 0o755), Err(LinuxError::EACCES));
+        assert_eq!(
+            open(&mut model, "noexec/x", OpenFlags::RDONLY),
+            Err(LinuxError::EACCES)
+        );
+        let chdir = Chdir::new(path("noexec"));
+        assert_eq!(model.execute(&chdir), Err(LinuxError::EACCES));
+
+        // Read-only for everyone after the umask.
+        let mut model = model.with_umask(0o222);
+        let fd = open(&mut model, "f", OpenFlags::CREAT | OpenFlags::WRONLY).unwrap();
+        model.execute(&Close::new(fd)).unwrap();
+        open(&mut model, "f", OpenFlags::RDONLY).unwrap();
+        assert_eq!(
+            open(&mut model, "f", OpenFlags::WRONLY),
+            Err(LinuxError::EACCES)
+        );
+        // Other users get the other permission bits.
+        let mut model = model.with_credentials(1001, 1001);
+        assert_eq!(mkdir(&mut model, "d", 0o755), Ok(()));
+        assert_eq!(
+            open(&mut model, "f", OpenFlags::RDWR),
+            Err(LinuxError::EACCES)
+        );
+
+        // Root bypasses permission checks.
+        let mut model = model.with_credentials(0, 0);
+        open(&mut model, "f", OpenFlags::WRONLY).unwrap();
+        open(&mut model, "ro/f", OpenFlags::CREAT).unwrap();
+        model.execute(&chdir).unwrap();
+    }
+
+    #[test]
+    fn fd_limit() {
+        let mut model = FsModel::new().with_max_fds(4);
+        assert_eq!(model.execute(&Dup::new(0)).unwrap().fd, 3);
+        assert_eq!(model.execute(&Dup::new(0)), Err(LinuxError::EMFILE));
+        model.execute(&Close::new(1)).unwrap();
+        assert_eq!(model.execute(&Dup::new(0)).unwrap().fd, 1);
+        assert_eq!(model.fds().collect::<Vec<_>>(), [0, 1, 2, 3]);
+    }
+}
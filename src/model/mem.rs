@@ -0,0 +1,554 @@
+use super::Execute;
+use crate::{
+    error::LinuxError,
+    introspect::VmRegion,
+    mem::{Brk, Mmap, MmapFlags, Mprotect, Munmap, ProtFlags, Sbrk, PAGE_SIZE},
+};
+use alloc::{collections::BTreeMap, vec::Vec};
+
+/// Lowest address that can be mapped, like Linux's default `mmap_min_addr`.
+pub const MMAP_MIN_ADDR: usize = 0x10000;
+
+/// Default end of the user address space, that of x86-64 with 4-level
+/// page tables.
+pub const DEFAULT_TASK_SIZE: usize = 1 << 47;
+
+/// Flags that only affect how a mapping is created, not the mapping itself.
+const TRANSIENT_FLAGS: MmapFlags = MmapFlags::MAP_FIXED
+    .union(MmapFlags::MAP_FIXED_NOREPLACE)
+    .union(MmapFlags::MAP_POPULATE);
+
+/// Round `addr` up to a page boundary, or `None` on overflow.
+fn page_align_up(addr: usize) -> Option<usize> {
+    addr.checked_next_multiple_of(PAGE_SIZE)
+}
+
+/// A mapping of the model, keyed by its start address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Vma {
+    end: usize,
+    prot: ProtFlags,
+    flags: MmapFlags,
+    fd: isize,
+    offset: usize,
+}
+
+impl Vma {
+    /// File offset of the mapping starting `by` bytes further, zero for
+    /// anonymous mappings.
+    fn offset_at(&self, by: usize) -> usize {
+        if self.flags.contains(MmapFlags::MAP_ANONYMOUS) {
+            0
+        } else {
+            self.offset + by
+        }
+    }
+
+    /// Check if the mapping at `start` continues `prev`, which ends there, so
+    /// that both are reported as one region.
+    fn continues(&self, prev: &VmRegion, prev_fd: isize) -> bool {
+        let anonymous = self.flags.contains(MmapFlags::MAP_ANONYMOUS);
+        prev.prot == self.prot
+            && prev.flags == self.flags
+            && (anonymous || (prev_fd == self.fd && prev.offset + prev.len() == self.offset as u64))
+    }
+}
+
+/// In-memory virtual address space of a single process, with its mappings
+/// and program break.
+///
+/// Mappings without `MAP_FIXED` are placed top-down below the mmap base,
+/// like Linux does, so the model only predicts their addresses if it is
+/// created with the same layout as the kernel under test. File contents
+/// are not modelled and file descriptors are assumed to be valid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemModel {
+    vmas: BTreeMap<usize, Vma>,
+    heap_start: usize,
+    brk: usize,
+    mmap_base: usize,
+    task_size: usize,
+}
+
+impl MemModel {
+    /// Create an empty address space with the program break at `brk` and
+    /// mappings placed below `mmap_base`.
+    pub fn new(brk: usize, mmap_base: usize) -> Self {
+        Self {
+            vmas: BTreeMap::new(),
+            heap_start: page_align_up(brk).expect("program break is below the address space end"),
+            brk,
+            mmap_base,
+            task_size: DEFAULT_TASK_SIZE,
+        }
+    }
+
+    /// Set the end of the user address space.
+    pub fn with_task_size(mut self, task_size: usize) -> Self {
+        self.task_size = task_size;
+        self
+    }
+
+    /// Current program break.
+    pub fn brk(&self) -> usize {
+        self.brk
+    }
+
+    /// Mapped regions sorted by start address, with adjacent compatible
+    /// mappings merged like in `/proc/self/maps`, to be compared against
+    /// the [`VmRegions`](crate::introspect::VmRegions) reported by the
+    /// kernel under test. The heap is reported like any anonymous mapping.
+    pub fn regions(&self) -> Vec<VmRegion> {
+        let mut regions: Vec<VmRegion> = Vec::with_capacity(self.vmas.len());
+        let mut prev_fd = -1;
+        for (&start, vma) in &self.vmas {
+            if let Some(prev) = regions.last_mut() {
+                if prev.end == start as u64 && vma.continues(prev, prev_fd) {
+                    prev.end = vma.end as u64;
+                    continue;
+                }
+            }
+            regions.push(VmRegion {
+                start: start as u64,
+                end: vma.end as u64,
+                prot: vma.prot,
+                flags: vma.flags,
+                offset: vma.offset as u64,
+            });
+            prev_fd = vma.fd;
+        }
+        regions
+    }
+
+    /// Region containing `addr`, if it is mapped.
+    pub fn region(&self, addr: usize) -> Option<VmRegion> {
+        self.regions()
+            .into_iter()
+            .find(|region| region.contains(addr as u64))
+    }
+
+    /// Check if no mapping overlaps `start..end`.
+    fn is_free(&self, start: usize, end: usize) -> bool {
+        self.vmas
+            .range(..end)
+            .next_back()
+            .is_none_or(|(_, vma)| vma.end <= start)
+    }
+
+    /// Check if every page of `start..end` is mapped.
+    fn is_mapped(&self, start: usize, end: usize) -> bool {
+        let mut pos = start;
+        for (&vma_start, vma) in self.vmas.range(..end) {
+            if vma.end <= pos {
+                continue;
+            }
+            if vma_start > pos {
+                return false;
+            }
+            pos = vma.end;
+        }
+        pos >= end
+    }
+
+    /// Remove mappings in `start..end`, splitting those partially inside.
+    fn unmap(&mut self, start: usize, end: usize) {
+        let overlapping: Vec<usize> = self
+            .vmas
+            .range(..end)
+            .filter(|(_, vma)| vma.end > start)
+            .map(|(&vma_start, _)| vma_start)
+            .collect();
+        for vma_start in overlapping {
+            let vma = self.vmas.remove(&vma_start).unwrap();
+            if vma_start < start {
+                let left = Vma {
+                    end: start,
+                    ..vma.clone()
+                };
+                self.vmas.insert(vma_start, left);
+            }
+            if vma.end > end {
+                let right = Vma {
+                    offset: vma.offset_at(end - vma_start),
+                    ..vma
+                };
+                self.vmas.insert(end, right);
+            }
+        }
+    }
+
+    /// Highest free range of `len` bytes below the mmap base.
+    fn find_free(&self, len: usize) -> Option<usize> {
+        let mut end = self.mmap_base.min(self.task_size);
+        for (&start, vma) in self.vmas.range(..end).rev() {
+            if vma.end < end && end - vma.end >= len {
+                break;
+            }
+            end = end.min(start);
+        }
+        let start = end.checked_sub(len)?;
+        (start >= MMAP_MIN_ADDR).then_some(start)
+    }
+
+    /// Move the program break to `addr`, return the new break, or the old
+    /// one if it cannot be moved.
+    fn set_brk(&mut self, addr: usize) -> usize {
+        if addr < self.heap_start || addr > self.task_size {
+            return self.brk;
+        }
+        let (Some(old_top), Some(new_top)) = (page_align_up(self.brk), page_align_up(addr)) else {
+            return self.brk;
+        };
+        if new_top > old_top {
+            if !self.is_free(old_top, new_top) {
+                return self.brk;
+            }
+            let heap = Vma {
+                end: new_top,
+                prot: ProtFlags::READ | ProtFlags::WRITE,
+                flags: MmapFlags::MAP_PRIVATE | MmapFlags::MAP_ANONYMOUS,
+                fd: -1,
+                offset: 0,
+            };
+            self.vmas.insert(old_top, heap);
+        } else if new_top < old_top {
+            self.unmap(new_top, old_top);
+        }
+        self.brk = addr;
+        addr
+    }
+
+    fn mmap(&mut self, cmd: &Mmap) -> Result<usize, LinuxError> {
+        let sharing = cmd.flags & (MmapFlags::MAP_SHARED | MmapFlags::MAP_PRIVATE);
+        if cmd.len == 0 || sharing.bits().count_ones() != 1 {
+            return Err(LinuxError::EINVAL);
+        }
+        let anonymous = cmd.flags.contains(MmapFlags::MAP_ANONYMOUS);
+        if !anonymous {
+            if !cmd.offset.is_multiple_of(PAGE_SIZE) {
+                return Err(LinuxError::EINVAL);
+            }
+            if cmd.fd < 0 {
+                return Err(LinuxError::EBADF);
+            }
+        }
+        let len = page_align_up(cmd.len).ok_or(LinuxError::ENOMEM)?;
+        if len > self.task_size {
+            return Err(LinuxError::ENOMEM);
+        }
+        let fixed = cmd
+            .flags
+            .intersects(MmapFlags::MAP_FIXED | MmapFlags::MAP_FIXED_NOREPLACE);
+        let start = if fixed {
+            if !cmd.addr.is_multiple_of(PAGE_SIZE) {
+                return Err(LinuxError::EINVAL);
+            }
+            if cmd.addr > self.task_size - len {
+                return Err(LinuxError::ENOMEM);
+            }
+            if cmd.addr < MMAP_MIN_ADDR {
+                return Err(LinuxError::EPERM);
+            }
+            if !cmd.flags.contains(MmapFlags::MAP_FIXED) && !self.is_free(cmd.addr, cmd.addr + len)
+            {
+                return Err(LinuxError::EEXIST);
+            }
+            cmd.addr
+        } else {
+            let hint = page_align_up(cmd.addr).filter(|&hint| {
+                hint >= MMAP_MIN_ADDR
+                    && hint <= self.task_size - len
+                    && self.is_free(hint, hint + len)
+            });
+            match hint {
+                Some(hint) => hint,
+                None => self.find_free(len).ok_or(LinuxError::ENOMEM)?,
+            }
+        };
+        self.unmap(start, start + len);
+        let vma = Vma {
+            end: start + len,
+            prot: cmd.prot,
+            flags: cmd.flags - TRANSIENT_FLAGS,
+            fd: if anonymous { -1 } else { cmd.fd },
+            offset: if anonymous { 0 } else { cmd.offset },
+        };
+        self.vmas.insert(start, vma);
+        Ok(start)
+    }
+
+    fn mprotect(&mut self, cmd: &Mprotect) -> Result<(), LinuxError> {
+        if !cmd.start.is_multiple_of(PAGE_SIZE) {
+            return Err(LinuxError::EINVAL);
+        }
+        let end = cmd
+            .start
+            .checked_add(cmd.len)
+            .and_then(page_align_up)
+            .ok_or(LinuxError::ENOMEM)?;
+        if end == cmd.start {
+            return Ok(());
+        }
+        if !self.is_mapped(cmd.start, end) {
+            return Err(LinuxError::ENOMEM);
+        }
+        let changed: Vec<(usize, Vma)> = self
+            .vmas
+            .range(..end)
+            .filter(|(_, vma)| vma.end > cmd.start)
+            .map(|(&start, vma)| (start, vma.clone()))
+            .collect();
+        self.unmap(cmd.start, end);
+        for (start, vma) in changed {
+            let new_start = start.max(cmd.start);
+            let vma = Vma {
+                end: vma.end.min(end),
+                prot: cmd.flags,
+                offset: vma.offset_at(new_start - start),
+                ..vma
+            };
+            self.vmas.insert(new_start, vma);
+        }
+        Ok(())
+    }
+}
+
+impl Execute<Brk> for MemModel {
+    /// The new program break, or the old one if it cannot be moved.
+    type Output = usize;
+
+    fn execute(&mut self, cmd: &Brk) -> Result<usize, LinuxError> {
+        Ok(self.set_brk(cmd.addr))
+    }
+}
+
+impl Execute<Sbrk> for MemModel {
+    /// The old program break.
+    type Output = usize;
+
+    fn execute(&mut self, cmd: &Sbrk) -> Result<usize, LinuxError> {
+        let old = self.brk;
+        let new = old
+            .checked_add_signed(cmd.increment)
+            .ok_or(LinuxError::ENOMEM)?;
+        if self.set_brk(new) != new {
+            return Err(LinuxError::ENOMEM);
+        }
+        Ok(old)
+    }
+}
+
+impl Execute<Mmap> for MemModel {
+    /// Address of the new mapping.
+    type Output = usize;
+
+    fn execute(&mut self, cmd: &Mmap) -> Result<usize, LinuxError> {
+        self.mmap(cmd)
+    }
+}
+
+impl Execute<Munmap> for MemModel {
+    type Output = ();
+
+    fn execute(&mut self, cmd: &Munmap) -> Result<(), LinuxError> {
+        if !cmd.addr.is_multiple_of(PAGE_SIZE) || cmd.len == 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let end = cmd
+            .addr
+            .checked_add(cmd.len)
+            .and_then(page_align_up)
+            .filter(|&end| end <= self.task_size)
+            .ok_or(LinuxError::EINVAL)?;
+        self.unmap(cmd.addr, end);
+        Ok(())
+    }
+}
+
+impl Execute<Mprotect> for MemModel {
+    type Output = ();
+
+    fn execute(&mut self, cmd: &Mprotect) -> Result<(), LinuxError> {
+        self.mprotect(cmd)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BRK: usize = 0x10_0000;
+    const MMAP_BASE: usize = 0x7000_0000_0000;
+    const ADDR: usize = 0x4000_0000;
+    const RW: ProtFlags = ProtFlags::READ.union(ProtFlags::WRITE);
+
+    fn model() -> MemModel {
+        MemModel::new(BRK, MMAP_BASE)
+    }
+
+    fn map_fixed(model: &mut MemModel, addr: usize, pages: usize) -> Result<usize, LinuxError> {
+        model.execute(&Mmap::builder(pages * PAGE_SIZE).fixed(addr).build())
+    }
+
+    fn spans(model: &MemModel) -> Vec<(usize, usize)> {
+        model
+            .regions()
+            .iter()
+            .map(|region| (region.start as usize, region.end as usize))
+            .collect()
+    }
+
+    #[test]
+    fn mmap_top_down() {
+        let mut model = model();
+        let first = model.execute(&Mmap::builder(1).build()).unwrap();
+        let second = model.execute(&Mmap::builder(PAGE_SIZE).build()).unwrap();
+        assert_eq!(first, MMAP_BASE - PAGE_SIZE);
+        assert_eq!(second, MMAP_BASE - 2 * PAGE_SIZE);
+        assert_eq!(spans(&model), [(second, MMAP_BASE)]);
+        // A free hint is used as is.
+        let hinted = Mmap::builder(PAGE_SIZE).addr(ADDR).build();
+        assert_eq!(model.execute(&hinted), Ok(ADDR));
+        assert_eq!(
+            model.execute(&Mmap::builder(0).build()),
+            Err(LinuxError::EINVAL)
+        );
+        let file = Mmap::builder(PAGE_SIZE).file(-1, 0).build();
+        assert_eq!(model.execute(&file), Err(LinuxError::EBADF));
+    }
+
+    #[test]
+    fn munmap_splits() {
+        let mut model = model();
+        map_fixed(&mut model, ADDR, 4).unwrap();
+        model
+            .execute(&Munmap::new(ADDR + PAGE_SIZE, PAGE_SIZE))
+            .unwrap();
+        assert_eq!(
+            spans(&model),
+            [
+                (ADDR, ADDR + PAGE_SIZE),
+                (ADDR + 2 * PAGE_SIZE, ADDR + 4 * PAGE_SIZE)
+            ]
+        );
+        // Unmapping free memory is not an error.
+        model.execute(&Munmap::new(ADDR, 8 * PAGE_SIZE)).unwrap();
+        assert!(model.regions().is_empty());
+        assert_eq!(
+            model.execute(&Munmap::new(ADDR + 1, PAGE_SIZE)),
+            Err(LinuxError::EINVAL)
+        );
+
+        // The file offset follows the start of a split file mapping.
+        let file = Mmap::builder(3 * PAGE_SIZE)
+            .fixed(ADDR)
+            .file(3, PAGE_SIZE)
+            .build();
+        model.execute(&file).unwrap();
+        model.execute(&Munmap::new(ADDR, PAGE_SIZE)).unwrap();
+        let region = model.region(ADDR + PAGE_SIZE).unwrap();
+        assert_eq!(region.start as usize, ADDR + PAGE_SIZE);
+        assert_eq!(region.offset as usize, 2 * PAGE_SIZE);
+        assert_eq!(model.region(ADDR), None);
+    }
+
+    #[test]
+    fn mprotect_splits() {
+        let mut model = model();
+        map_fixed(&mut model, ADDR, 3).unwrap();
+        let protect = |start, prot| Mprotect::new(start, PAGE_SIZE, prot);
+        model
+            .execute(&protect(ADDR + PAGE_SIZE, ProtFlags::READ))
+            .unwrap();
+        let regions = model.regions();
+        assert_eq!(regions.len(), 3);
+        assert_eq!(regions[1].prot, ProtFlags::READ);
+        assert_eq!((regions[0].prot, regions[2].prot), (RW, RW));
+        // Restoring the protection merges the regions again.
+        model.execute(&protect(ADDR + PAGE_SIZE, RW)).unwrap();
+        assert_eq!(spans(&model), [(ADDR, ADDR + 3 * PAGE_SIZE)]);
+
+        assert_eq!(
+            model.execute(&protect(ADDR + 3 * PAGE_SIZE, RW)),
+            Err(LinuxError::ENOMEM)
+        );
+        assert_eq!(
+            model.execute(&protect(ADDR + 1, RW)),
+            Err(LinuxError::EINVAL)
+        );
+        let spanning = Mprotect::new(ADDR + 2 * PAGE_SIZE, 2 * PAGE_SIZE, ProtFlags::READ);
+        assert_eq!(model.execute(&spanning), Err(LinuxError::ENOMEM));
+        assert_eq!(model.region(ADDR + 2 * PAGE_SIZE).unwrap().prot, RW);
+    }
+
+    #[test]
+    fn fixed_noreplace() {
+        let mut model = model();
+        map_fixed(&mut model, ADDR, 2).unwrap();
+        let noreplace = |addr| {
+            Mmap::builder(PAGE_SIZE)
+                .addr(addr)
+                .prot(ProtFlags::READ)
+                .flags(MmapFlags::MAP_FIXED_NOREPLACE)
+                .build()
+        };
+        assert_eq!(
+            model.execute(&noreplace(ADDR + PAGE_SIZE)),
+            Err(LinuxError::EEXIST)
+        );
+        assert_eq!(
+            model.execute(&noreplace(ADDR + 2 * PAGE_SIZE)),
+            Ok(ADDR + 2 * PAGE_SIZE)
+        );
+        let region = model.region(ADDR + 2 * PAGE_SIZE).unwrap();
+        assert!(!region.flags.contains(MmapFlags::MAP_FIXED_NOREPLACE));
+        assert_eq!(model.execute(&noreplace(0)), Err(LinuxError::EPERM));
+
+        // MAP_FIXED replaces the existing mapping.
+        let fixed = Mmap::builder(PAGE_SIZE)
+            .fixed(ADDR + PAGE_SIZE)
+            .prot(ProtFlags::READ)
+            .build();
+        assert_eq!(model.execute(&fixed), Ok(ADDR + PAGE_SIZE));
+        assert_eq!(
+            spans(&model),
+            [
+                (ADDR, ADDR + PAGE_SIZE),
+                (ADDR + PAGE_SIZE, ADDR + 3 * PAGE_SIZE)
+            ]
+        );
+    }
+
+    #[test]
+    fn brk() {
+        let mut model = model();
+        let top = BRK + 4 * PAGE_SIZE;
+        assert_eq!(model.execute(&Brk::new(top - 1)), Ok(top - 1));
+        assert_eq!(spans(&model), [(BRK, top)]);
+        // Shrinking unmaps the pages above the new break.
+        assert_eq!(
+            model.execute(&Brk::new(BRK + PAGE_SIZE)),
+            Ok(BRK + PAGE_SIZE)
+        );
+        assert_eq!(spans(&model), [(BRK, BRK + PAGE_SIZE)]);
+        // The break cannot move below its start.
+        assert_eq!(
+            model.execute(&Brk::new(BRK - PAGE_SIZE)),
+            Ok(BRK + PAGE_SIZE)
+        );
+
+        assert_eq!(
+            model.execute(&Sbrk::new(-(PAGE_SIZE as isize))),
+            Ok(BRK + PAGE_SIZE)
+        );
+        assert_eq!(model.brk(), BRK);
+        assert!(model.regions().is_empty());
+
+        // The heap cannot grow into a mapping.
+        map_fixed(&mut model, BRK + 2 * PAGE_SIZE, 1).unwrap();
+        let grow = Sbrk::new(3 * PAGE_SIZE as isize);
+        assert_eq!(model.execute(&grow), Err(LinuxError::ENOMEM));
+        assert_eq!(model.brk(), BRK);
+        assert_eq!(model.execute(&Sbrk::new(PAGE_SIZE as isize)), Ok(BRK));
+    }
+}
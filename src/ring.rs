@@ -0,0 +1,280 @@
+//! The ring buffer has a fixed `repr(C)` layout, so it can live in memory
+//! shared between checker and harness (e.g. `/dev/shm` or ivshmem) as well as
+//! in ordinary memory. Each frame is a little-endian `u32` length followed by
+//! the frame bytes, possibly wrapping around the end of the data area.
+//!
+//! The channel is single-producer single-consumer: at any time only one party
+//! may push and only one party may pop.
+
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+/// Size of the length prefix of a frame.
+const LEN_SIZE: usize = core::mem::size_of::<u32>();
+
+/// The length prefix of the next frame exceeds the bytes in use, e.g. because
+/// the peer corrupted the shared memory. Holds the bad length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BadLength(pub usize);
+
+/// Lock-free SPSC ring buffer of `N` bytes. `N` must be a power of two.
+#[repr(C)]
+pub struct RingBuffer<const N: usize> {
+    /// Total bytes pushed, wrapping. Written by the producer.
+    head: AtomicU32,
+    /// Total bytes popped, wrapping. Written by the consumer.
+    tail: AtomicU32,
+    data: UnsafeCell<[u8; N]>,
+}
+
+unsafe impl<const N: usize> Sync for RingBuffer<N> {}
+
+impl<const N: usize> Default for RingBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> RingBuffer<N> {
+    const CHECK: () = assert!(
+        N.is_power_of_two() && N <= 1 << 31,
+        "capacity must be a power of two not above 2^31"
+    );
+
+    /// Create an empty ring buffer.
+    pub const fn new() -> Self {
+        let () = Self::CHECK;
+        Self {
+            head: AtomicU32::new(0),
+            tail: AtomicU32::new(0),
+            data: UnsafeCell::new([0; N]),
+        }
+    }
+
+    /// View shared memory at `ptr` as a ring buffer.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be aligned for `RingBuffer<N>` and point to a region of at
+    /// least `size_of::<RingBuffer<N>>()` bytes that stays valid for `'a`,
+    /// initialized by [`RingBuffer::init`] or zeroed.
+    pub unsafe fn from_ptr<'a>(ptr: *mut u8) -> &'a Self {
+        let () = Self::CHECK;
+        &*(ptr as *const Self)
+    }
+
+    /// Reset to the empty state. Must not race with `push` or `pop`.
+    pub fn init(&self) {
+        self.head.store(0, Ordering::Relaxed);
+        self.tail.store(0, Ordering::Release);
+    }
+
+    /// Number of bytes in use, including length prefixes. Capped at `N` if
+    /// the peer corrupted the positions.
+    pub fn used(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Acquire);
+        Self::span(head, tail).unwrap_or(N)
+    }
+
+    /// Bytes between `tail` and `head`, or `None` if they are more than `N`
+    /// apart, which only happens if the peer corrupted them.
+    fn span(head: u32, tail: u32) -> Option<usize> {
+        let used = head.wrapping_sub(tail) as usize;
+        (used <= N).then_some(used)
+    }
+
+    /// Check if there is no frame to pop.
+    pub fn is_empty(&self) -> bool {
+        self.used() == 0
+    }
+
+    fn write_at(&self, pos: u32, bytes: &[u8]) {
+        let data = self.data.get() as *mut u8;
+        for (i, &byte) in bytes.iter().enumerate() {
+            let idx = pos.wrapping_add(i as u32) as usize % N;
+            unsafe { data.add(idx).write_volatile(byte) };
+        }
+    }
+
+    fn read_at(&self, pos: u32, bytes: &mut [u8]) {
+        let data = self.data.get() as *const u8;
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            let idx = pos.wrapping_add(i as u32) as usize % N;
+            *byte = unsafe { data.add(idx).read_volatile() };
+        }
+    }
+
+    /// Producer: push a frame made of the concatenation of `parts`. Return
+    /// `false` if there is not enough free space.
+    pub fn push_parts(&self, parts: &[&[u8]]) -> bool {
+        let len: usize = parts.iter().map(|part| part.len()).sum();
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+        let Some(used) = Self::span(head, tail) else {
+            return false;
+        };
+        if LEN_SIZE + len > N - used {
+            return false;
+        }
+        self.write_at(head, &(len as u32).to_le_bytes());
+        let mut pos = head.wrapping_add(LEN_SIZE as u32);
+        for part in parts {
+            self.write_at(pos, part);
+            pos = pos.wrapping_add(part.len() as u32);
+        }
+        self.head.store(pos, Ordering::Release);
+        true
+    }
+
+    /// Producer: push a frame. Return `false` if there is not enough free space.
+    pub fn push(&self, frame: &[u8]) -> bool {
+        self.push_parts(&[frame])
+    }
+
+    /// Producer: push the command message of `cmd` with id `id`, i.e. the
+    /// command id followed by the encoded command.
+    pub fn push_command(&self, id: usize, cmd: &[u8]) -> bool {
+        self.push_parts(&[&crate::id_to_wire(id), cmd])
+    }
+
+    /// Consumer: length of the next frame, if any. Fail if the length prefix
+    /// exceeds the bytes in use, or those exceed the capacity.
+    pub fn next_len(&self) -> Result<Option<usize>, BadLength> {
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Relaxed);
+        if head == tail {
+            return Ok(None);
+        }
+        let mut len = [0; LEN_SIZE];
+        self.read_at(tail, &mut len);
+        let len = u32::from_le_bytes(len) as usize;
+        let used = Self::span(head, tail).ok_or(BadLength(len))?;
+        if used < LEN_SIZE || len > used - LEN_SIZE {
+            return Err(BadLength(len));
+        }
+        Ok(Some(len))
+    }
+
+    /// Consumer: pop the next frame into `buf`, return its length. Return
+    /// `None` and keep the frame if there is none or `buf` is too small, and
+    /// fail if the length prefix exceeds the bytes in use.
+    pub fn pop(&self, buf: &mut [u8]) -> Result<Option<usize>, BadLength> {
+        let Some(len) = self.next_len()? else {
+            return Ok(None);
+        };
+        let Some(frame) = buf.get_mut(..len) else {
+            return Ok(None);
+        };
+        let tail = self.tail.load(Ordering::Relaxed);
+        let pos = tail.wrapping_add(LEN_SIZE as u32);
+        self.read_at(pos, frame);
+        self.tail
+            .store(pos.wrapping_add(len as u32), Ordering::Release);
+        Ok(Some(len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_pop() {
+        let ring = RingBuffer::<16>::new();
+        let mut buf = [0; 16];
+        assert!(ring.is_empty());
+        assert_eq!(ring.pop(&mut buf), Ok(None));
+        assert!(ring.push(&[1, 2, 3]));
+        assert!(ring.push_command(9, &[4]));
+        assert_eq!(ring.used(), 2 * LEN_SIZE + 3 + crate::ID_SIZE + 1);
+        assert_eq!(ring.pop(&mut buf), Ok(Some(3)));
+        assert_eq!(buf[..3], [1, 2, 3]);
+        assert_eq!(ring.pop(&mut buf), Ok(Some(5)));
+        assert_eq!(buf[..5], [9, 0, 0, 0, 4]);
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn full_and_small_buffer() {
+        let ring = RingBuffer::<16>::new();
+        assert!(!ring.push(&[0; 13]));
+        assert!(ring.push(&[0; 12]));
+        assert!(!ring.push(&[]));
+        let mut small = [0; 4];
+        assert_eq!(ring.pop(&mut small), Ok(None));
+        assert_eq!(ring.next_len(), Ok(Some(12)));
+        let mut buf = [0; 12];
+        assert_eq!(ring.pop(&mut buf), Ok(Some(12)));
+    }
+
+    #[test]
+    fn wrap_around() {
+        let ring = RingBuffer::<16>::new();
+        let mut buf = [0; 16];
+        for i in 0..10u8 {
+            let frame = [i; 7];
+            assert!(ring.push(&frame));
+            assert_eq!(ring.pop(&mut buf), Ok(Some(7)));
+            assert_eq!(buf[..7], frame);
+        }
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn from_ptr() {
+        let mut shared = RingBuffer::<16>::new();
+        let ptr = &mut shared as *mut RingBuffer<16> as *mut u8;
+        // SAFETY: `shared` is a ring buffer that outlives both views.
+        let (producer, consumer) = unsafe {
+            (
+                RingBuffer::<16>::from_ptr(ptr),
+                RingBuffer::<16>::from_ptr(ptr),
+            )
+        };
+        producer.init();
+        assert!(producer.push(&[5]));
+        let mut buf = [0; 1];
+        assert_eq!(consumer.pop(&mut buf), Ok(Some(1)));
+        assert_eq!(buf, [5]);
+    }
+
+    #[test]
+    fn bad_length() {
+        let ring = RingBuffer::<16>::new();
+        assert!(ring.push(&[1, 2]));
+        ring.write_at(0, &7u32.to_le_bytes());
+        let mut buf = [0; 16];
+        assert_eq!(ring.next_len(), Err(BadLength(7)));
+        assert_eq!(ring.pop(&mut buf), Err(BadLength(7)));
+        assert_eq!(ring.used(), LEN_SIZE + 2);
+    }
+
+    #[test]
+    fn corrupt_positions() {
+        let ring = RingBuffer::<16>::new();
+        assert!(ring.push(&[1]));
+        // The peer moved `tail` past `head`.
+        ring.tail.store(8, Ordering::Relaxed);
+        assert_eq!(ring.used(), 16);
+        assert!(!ring.push(&[2]));
+        let mut buf = [0; 16];
+        assert!(ring.pop(&mut buf).is_err());
+    }
+
+    #[test]
+    fn positions_wrap() {
+        let ring = RingBuffer::<16>::new();
+        let start = u32::MAX - 2;
+        ring.head.store(start, Ordering::Relaxed);
+        ring.tail.store(start, Ordering::Relaxed);
+        let mut buf = [0; 16];
+        for i in 0..4u8 {
+            assert!(ring.push(&[i; 5]));
+            assert_eq!(ring.pop(&mut buf), Ok(Some(5)));
+            assert_eq!(buf[..5], [i; 5]);
+        }
+    }
+}
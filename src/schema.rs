@@ -0,0 +1,148 @@
+use crate::{
+    fs::{FileMode, OpenFlags, UnlinkatFlags},
+    mem::{MmapFlags, ProtFlags},
+    CommandDesc, COMMANDS,
+};
+use alloc::string::String;
+use bitflags::Flags;
+use core::fmt::Write;
+
+/// Export the schema of all commands and flag types as JSON.
+///
+/// The output has the form
+/// `{"commands": [{"id", "name", "module", "version", "has_response",
+/// "fields": [{"name", "type", "since", "default"}]}],
+/// "flags": [{"name", "values": [{"name", "value"}]}]}`.
+pub fn to_json() -> String {
+    let mut out = String::new();
+    out.push_str("{\"commands\":[");
+    for (i, desc) in COMMANDS.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_command(&mut out, desc);
+    }
+    out.push_str("],\"flags\":[");
+    write_flags::<OpenFlags>(&mut out, "OpenFlags");
+    out.push(',');
+    write_flags::<FileMode>(&mut out, "FileMode");
+    out.push(',');
+    write_flags::<UnlinkatFlags>(&mut out, "UnlinkatFlags");
+    out.push(',');
+    write_flags::<ProtFlags>(&mut out, "ProtFlags");
+    out.push(',');
+    write_flags::<MmapFlags>(&mut out, "MmapFlags");
+    out.push_str("]}");
+    out
+}
+
+fn write_command(out: &mut String, desc: &CommandDesc) {
+    write!(out, "{{\"id\":{},\"name\":", desc.id).unwrap();
+    write_str(out, desc.name);
+    out.push_str(",\"module\":");
+    write_str(out, desc.module);
+    write!(
+        out,
+        ",\"version\":{},\"has_response\":{},\"fields\":[",
+        desc.version, desc.has_response
+    )
+    .unwrap();
+    for (i, field) in desc.fields.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str("{\"name\":");
+        write_str(out, field.name);
+        out.push_str(",\"type\":");
+        write_str(out, field.ty);
+        write!(
+            out,
+            ",\"since\":{},\"default\":{}}}",
+            field.since, field.default
+        )
+        .unwrap();
+    }
+    out.push_str("]}");
+}
+
+fn write_flags<F>(out: &mut String, name: &str)
+where
+    F: Flags,
+    F::Bits: Into<u64>,
+{
+    out.push_str("{\"name\":");
+    write_str(out, name);
+    out.push_str(",\"values\":[");
+    for (i, flag) in F::FLAGS.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str("{\"name\":");
+        write_str(out, flag.name());
+        write!(out, ",\"value\":{}}}", flag.value().bits().into()).unwrap();
+    }
+    out.push_str("]}");
+}
+
+/// Write a JSON string literal.
+fn write_str(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check that brackets are balanced and strings terminated, ignoring
+    /// escaped characters inside strings.
+    fn check_balanced(json: &str) {
+        let mut depth = alloc::vec::Vec::new();
+        let mut chars = json.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => assert!(chars.next().is_some()),
+                        Some(c) => assert!(c >= ' ', "control character in string"),
+                        None => panic!("unterminated string"),
+                    }
+                },
+                '{' => depth.push('}'),
+                '[' => depth.push(']'),
+                '}' | ']' => assert_eq!(depth.pop(), Some(c)),
+                _ => {}
+            }
+        }
+        assert!(depth.is_empty());
+    }
+
+    #[test]
+    fn json() {
+        let json = to_json();
+        check_balanced(&json);
+        assert!(json.starts_with("{\"commands\":[{"));
+        assert!(json.contains(
+            "{\"id\":57,\"name\":\"close\",\"module\":\"km_command::fs\",\"version\":1,\"has_response\":false,\
+             \"fields\":[{\"name\":\"fd\",\"type\":\"isize\",\"since\":1,\"default\":false}]}"
+        ));
+        assert!(json.contains("{\"name\":\"READ\",\"value\":1}"));
+    }
+
+    #[test]
+    fn escape() {
+        let mut out = String::new();
+        write_str(&mut out, "a\"b\\c\n");
+        assert_eq!(out, "\"a\\\"b\\\\c\\u000a\"");
+        check_balanced(&out);
+    }
+}
@@ -0,0 +1,173 @@
+use crate::fs::{
+    Chdir, Close, Dup, Fstat, Getcwd, Getdents, Getdents1, Linkat, Mkdirat, OpenFlags, Openat,
+    Path, Unlinkat, UnlinkatFlags, AT_FDCWD,
+};
+use alloc::{format, string::String};
+
+/// Approximate shell equivalent of a command, so that failing traces can be
+/// reproduced by hand on a reference system.
+///
+/// File descriptors are rendered as shell fd numbers, which only match if
+/// the shell opened the same files in the same order.
+pub trait ToShell {
+    /// Render the command as a shell command line.
+    fn to_shell(&self) -> String;
+}
+
+/// Quote `s` for the shell if it contains special characters.
+fn quote(s: &str) -> String {
+    let plain = !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"/._-+,:@%".contains(&b));
+    if plain {
+        String::from(s)
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+}
+
+/// Shell path of `path` resolved against `dirfd`.
+fn path_at(dirfd: isize, path: &Path) -> String {
+    if path.absolute() || dirfd == AT_FDCWD {
+        quote(path)
+    } else {
+        quote(&format!("/dev/fd/{}/{}", dirfd, path.as_str()))
+    }
+}
+
+impl ToShell for Openat {
+    fn to_shell(&self) -> String {
+        let path = path_at(self.dirfd, &self.path);
+        if self.flags.contains(OpenFlags::CREAT) {
+            format!("touch {} && chmod {:o} {}", path, self.mode.bits(), path)
+        } else if self.flags.contains(OpenFlags::TRUNC) {
+            format!("truncate -s 0 {}", path)
+        } else if self.flags.contains(OpenFlags::DIRECTORY) {
+            format!("test -d {}", path)
+        } else {
+            format!("test -e {}", path)
+        }
+    }
+}
+
+impl ToShell for Close {
+    fn to_shell(&self) -> String {
+        format!("exec {}>&-", self.fd)
+    }
+}
+
+impl ToShell for Fstat {
+    fn to_shell(&self) -> String {
+        format!("stat -L /dev/fd/{}", self.fd)
+    }
+}
+
+impl ToShell for Getdents {
+    fn to_shell(&self) -> String {
+        format!("ls -af /dev/fd/{}/", self.fd)
+    }
+}
+
+impl ToShell for Getdents1 {
+    fn to_shell(&self) -> String {
+        format!("ls -af /dev/fd/{}/ | head -n 1", self.fd)
+    }
+}
+
+impl ToShell for Linkat {
+    fn to_shell(&self) -> String {
+        format!(
+            "ln {} {}",
+            path_at(self.olddirfd, &self.oldpath),
+            path_at(self.newdirfd, &self.newpath)
+        )
+    }
+}
+
+impl ToShell for Unlinkat {
+    fn to_shell(&self) -> String {
+        let path = path_at(self.dirfd, &self.path);
+        if self.flags.contains(UnlinkatFlags::REMOVEDIR) {
+            format!("rmdir {}", path)
+        } else {
+            format!("rm {}", path)
+        }
+    }
+}
+
+impl ToShell for Mkdirat {
+    fn to_shell(&self) -> String {
+        format!(
+            "mkdir -m {:o} {}",
+            self.mode.bits(),
+            path_at(self.dirfd, &self.path)
+        )
+    }
+}
+
+impl ToShell for Getcwd {
+    fn to_shell(&self) -> String {
+        String::from("pwd")
+    }
+}
+
+impl ToShell for Dup {
+    fn to_shell(&self) -> String {
+        // The shell needs the new fd number, which the kernel picks.
+        format!("exec {{fd}}>&{}", self.oldfd)
+    }
+}
+
+impl ToShell for Chdir {
+    fn to_shell(&self) -> String {
+        format!("cd {}", quote(&self.path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::FileMode;
+
+    fn path(s: &str) -> Path {
+        Path::try_from(s).unwrap()
+    }
+
+    fn openat(dirfd: isize, p: &str, flags: OpenFlags) -> Openat {
+        Openat::new(dirfd, path(p), flags, FileMode::from_bits_truncate(0o640))
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote("/tmp/a-b_c.d"), "/tmp/a-b_c.d");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("a b"), "'a b'");
+        assert_eq!(quote("it's"), "'it'\\''s'");
+        assert_eq!(quote("$(rm -rf ~)"), "'$(rm -rf ~)'");
+        let cmd = Chdir::new(path("/my dir/it's"));
+        assert_eq!(cmd.to_shell(), "cd '/my dir/it'\\''s'");
+        let cmd = Unlinkat::new(3, path("a \"b\""), UnlinkatFlags::empty());
+        assert_eq!(cmd.to_shell(), "rm '/dev/fd/3/a \"b\"'");
+    }
+
+    #[test]
+    fn openat_flags() {
+        let flags = OpenFlags::CREAT | OpenFlags::TRUNC;
+        assert_eq!(
+            openat(AT_FDCWD, "new file", flags).to_shell(),
+            "touch 'new file' && chmod 640 'new file'"
+        );
+        assert_eq!(
+            openat(AT_FDCWD, "/f", OpenFlags::TRUNC).to_shell(),
+            "truncate -s 0 /f"
+        );
+        assert_eq!(
+            openat(4, "d", OpenFlags::DIRECTORY).to_shell(),
+            "test -d /dev/fd/4/d"
+        );
+        assert_eq!(
+            openat(4, "/abs", OpenFlags::empty()).to_shell(),
+            "test -e /abs"
+        );
+    }
+}
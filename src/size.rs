@@ -0,0 +1,70 @@
+use core::mem::size_of;
+
+/// Types with a known maximum encoded size.
+pub trait MaxSize {
+    /// Maximum number of bytes the encoding of this type can take.
+    const MAX_SIZE: usize;
+}
+
+/// Maximum size of an integer of `bytes` bytes encoded as a varint.
+pub const fn varint_max(bytes: usize) -> usize {
+    (bytes * 8).div_ceil(7)
+}
+
+/// Size of `value` encoded as a varint.
+pub const fn varint_size(mut value: usize) -> usize {
+    let mut size = 1;
+    while value >= 0x80 {
+        value >>= 7;
+        size += 1;
+    }
+    size
+}
+
+/// Maximum size of a versioned command frame with a payload of at
+/// most `payload` bytes.
+pub const fn frame_size(payload: usize) -> usize {
+    1 + varint_size(payload) + payload
+}
+
+/// The largest of `sizes`.
+pub const fn max_of(sizes: &[usize]) -> usize {
+    let mut max = 0;
+    let mut i = 0;
+    while i < sizes.len() {
+        if sizes[i] > max {
+            max = sizes[i];
+        }
+        i += 1;
+    }
+    max
+}
+
+macro_rules! impl_max_size {
+    ($($t:ty => $size:expr),* $(,)?) => {
+        $(
+            impl MaxSize for $t {
+                const MAX_SIZE: usize = $size;
+            }
+        )*
+    };
+}
+
+impl<T: MaxSize> MaxSize for Option<T> {
+    const MAX_SIZE: usize = 1 + T::MAX_SIZE;
+}
+
+impl_max_size! {
+    bool => 1,
+    u8 => 1,
+    i8 => 1,
+    u16 => varint_max(size_of::<u16>()),
+    i16 => varint_max(size_of::<i16>()),
+    u32 => varint_max(size_of::<u32>()),
+    i32 => varint_max(size_of::<i32>()),
+    u64 => varint_max(size_of::<u64>()),
+    i64 => varint_max(size_of::<i64>()),
+    // serde encodes `usize` and `isize` as 64-bit integers on every target.
+    usize => varint_max(size_of::<u64>()),
+    isize => varint_max(size_of::<i64>()),
+}
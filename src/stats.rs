@@ -0,0 +1,188 @@
+use crate::command_name;
+use alloc::{collections::BTreeMap, vec::Vec};
+use serde::{Deserialize, Serialize};
+
+/// Statistics of one command.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandStats {
+    /// Command id.
+    pub id: usize,
+    /// Number of times the command was sent.
+    pub sends: u64,
+    /// Number of responses received.
+    pub responses: u64,
+    /// Number of responses reporting an error.
+    pub errors: u64,
+    /// Total encoded size of all sends in bytes.
+    pub total_size: u64,
+    /// Smallest encoded size in bytes.
+    pub min_size: usize,
+    /// Largest encoded size in bytes.
+    pub max_size: usize,
+}
+
+impl CommandStats {
+    /// Name of the command, if the id is known.
+    pub fn name(&self) -> Option<&'static str> {
+        command_name(self.id)
+    }
+
+    /// Fraction of responses reporting an error, or 0 if there is none.
+    pub fn error_rate(&self) -> f64 {
+        if self.responses == 0 {
+            0.0
+        } else {
+            self.errors as f64 / self.responses as f64
+        }
+    }
+
+    /// Average encoded size in bytes, or 0 if never sent.
+    pub fn mean_size(&self) -> f64 {
+        if self.sends == 0 {
+            0.0
+        } else {
+            self.total_size as f64 / self.sends as f64
+        }
+    }
+}
+
+/// Summary of a run, ordered by command id.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatsReport {
+    /// Statistics of every command sent at least once.
+    pub commands: Vec<CommandStats>,
+}
+
+impl StatsReport {
+    /// Total number of sends.
+    pub fn total_sends(&self) -> u64 {
+        self.commands.iter().map(|stats| stats.sends).sum()
+    }
+
+    /// Total number of responses reporting an error.
+    pub fn total_errors(&self) -> u64 {
+        self.commands.iter().map(|stats| stats.errors).sum()
+    }
+
+    /// Ids of commands in `ids` that were never sent, e.g. all ids in
+    /// [`COMMANDS`](crate::COMMANDS) to judge the coverage of a run.
+    pub fn unsent<'a>(
+        &'a self,
+        ids: impl IntoIterator<Item = usize> + 'a,
+    ) -> impl Iterator<Item = usize> + 'a {
+        ids.into_iter()
+            .filter(|id| !self.commands.iter().any(|stats| stats.id == *id))
+    }
+}
+
+/// Collector of per-command statistics.
+#[derive(Debug, Clone, Default)]
+pub struct StatsCollector {
+    stats: BTreeMap<usize, CommandStats>,
+}
+
+impl StatsCollector {
+    /// Create an empty collector.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn entry(&mut self, id: usize) -> &mut CommandStats {
+        self.stats.entry(id).or_insert(CommandStats {
+            id,
+            min_size: usize::MAX,
+            ..Default::default()
+        })
+    }
+
+    /// Record that command `id` was sent encoded in `len` bytes.
+    pub fn record_send(&mut self, id: usize, len: usize) {
+        let stats = self.entry(id);
+        stats.sends += 1;
+        stats.total_size += len as u64;
+        stats.min_size = stats.min_size.min(len);
+        stats.max_size = stats.max_size.max(len);
+    }
+
+    /// Record a response to command `id`, and whether it reports an error.
+    pub fn record_response(&mut self, id: usize, error: bool) {
+        let stats = self.entry(id);
+        stats.responses += 1;
+        if error {
+            stats.errors += 1;
+        }
+    }
+
+    /// Record a response to command `id` carrying a syscall return value,
+    /// where negative values are errors.
+    pub fn record_ret(&mut self, id: usize, ret: isize) {
+        self.record_response(id, ret < 0);
+    }
+
+    /// Statistics of command `id`, if it was recorded.
+    pub fn get(&self, id: usize) -> Option<&CommandStats> {
+        self.stats.get(&id)
+    }
+
+    /// Clear all statistics.
+    pub fn clear(&mut self) {
+        self.stats.clear();
+    }
+
+    /// Build a report of the collected statistics.
+    pub fn report(&self) -> StatsReport {
+        StatsReport {
+            commands: self
+                .stats
+                .values()
+                .map(|stats| CommandStats {
+                    min_size: if stats.sends == 0 { 0 } else { stats.min_size },
+                    ..*stats
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::{Close, Getcwd};
+
+    #[test]
+    fn collect() {
+        let mut collector = StatsCollector::new();
+        collector.record_send(Close::ID, 3);
+        collector.record_send(Close::ID, 7);
+        collector.record_send(Close::ID, 5);
+        collector.record_ret(Close::ID, 0);
+        collector.record_ret(Close::ID, -9);
+        collector.record_response(Getcwd::ID, true);
+
+        let close = collector.get(Close::ID).unwrap();
+        assert_eq!((close.sends, close.responses, close.errors), (3, 2, 1));
+        assert_eq!(
+            (close.min_size, close.max_size, close.total_size),
+            (3, 7, 15)
+        );
+        assert_eq!(close.mean_size(), 5.0);
+        assert_eq!(close.error_rate(), 0.5);
+        assert_eq!(close.name(), Some("close"));
+        assert!(collector.get(0xdead).is_none());
+
+        let report = collector.report();
+        let ids: Vec<usize> = report.commands.iter().map(|stats| stats.id).collect();
+        let mut sorted = [Close::ID, Getcwd::ID];
+        sorted.sort();
+        assert_eq!(ids, sorted);
+        assert_eq!((report.total_sends(), report.total_errors()), (3, 2));
+        // Commands only answered report no sizes.
+        let getcwd = report.commands.iter().find(|s| s.id == Getcwd::ID).unwrap();
+        assert_eq!((getcwd.min_size, getcwd.mean_size()), (0, 0.0));
+        let unsent: Vec<usize> = report.unsent([Close::ID, 1, 2]).collect();
+        assert_eq!(unsent, [1, 2]);
+
+        collector.clear();
+        assert_eq!(collector.report(), StatsReport::default());
+    }
+}
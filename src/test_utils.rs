@@ -0,0 +1,78 @@
+use crate::{
+    bytes::{Bytes, FixedString},
+    control::{FaultSpec, InvariantKind},
+    envelope::TaskId,
+    error::LinuxError,
+    fs::{FileMode, OpenFlags, Path, UnlinkatFlags},
+    mem::{MmapFlags, ProtFlags},
+};
+
+/// Size of the buffer sample commands are encoded into.
+pub const MAX_SAMPLE_SIZE: usize = 4096;
+
+/// A sample value of a command field, used by the `check_roundtrip` method
+/// generated by [`command!`](crate::command).
+///
+/// Samples are chosen to exercise the encoding: integers are close to their
+/// limits, flags have all bits set and buffers are full.
+pub trait Sample {
+    /// The sample value.
+    fn sample() -> Self;
+}
+
+macro_rules! impl_sample {
+    ($($t:ty => $value:expr),* $(,)?) => {
+        $(
+            impl Sample for $t {
+                fn sample() -> Self {
+                    $value
+                }
+            }
+        )*
+    };
+}
+
+impl_sample! {
+    bool => true,
+    u8 => u8::MAX - 1,
+    i8 => i8::MIN + 1,
+    u16 => u16::MAX - 1,
+    i16 => i16::MIN + 1,
+    u32 => u32::MAX - 1,
+    i32 => i32::MIN + 1,
+    u64 => u64::MAX - 1,
+    i64 => i64::MIN + 1,
+    usize => usize::MAX - 1,
+    isize => isize::MIN + 1,
+    OpenFlags => OpenFlags::all(),
+    FileMode => FileMode::all(),
+    UnlinkatFlags => UnlinkatFlags::all(),
+    ProtFlags => ProtFlags::all(),
+    MmapFlags => MmapFlags::all(),
+    TaskId => TaskId(u32::MAX - 1),
+    InvariantKind => InvariantKind::Custom(u32::MAX - 1),
+    FaultSpec => FaultSpec::fail_syscall(usize::MAX - 1, LinuxError::ENOGRACE, u32::MAX - 1),
+    Path => Path(FixedString::sample()),
+}
+
+impl<T: Sample> Sample for Option<T> {
+    fn sample() -> Self {
+        Some(T::sample())
+    }
+}
+
+impl<const N: usize> Sample for FixedString<N> {
+    fn sample() -> Self {
+        let mut s = Self::new();
+        while s.push('a').is_ok() {}
+        s
+    }
+}
+
+impl<const N: usize> Sample for Bytes<N> {
+    fn sample() -> Self {
+        let mut bytes = Self::new();
+        while bytes.push(0xa5).is_ok() {}
+        bytes
+    }
+}
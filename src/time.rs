@@ -0,0 +1,154 @@
+use crate::error::LinuxError;
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+
+/// Nanoseconds per second.
+pub const NSEC_PER_SEC: i64 = 1_000_000_000;
+
+/// Microseconds per second.
+pub const USEC_PER_SEC: i64 = 1_000_000;
+
+/// Time in seconds and nanoseconds, the `timespec` of libc.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[repr(C)]
+pub struct TimeSpec {
+    /// Seconds.
+    pub sec: i64,
+    /// Nanoseconds, in range `[0, NSEC_PER_SEC)`.
+    pub nsec: i64,
+}
+
+impl TimeSpec {
+    /// Special `nsec` value for `utimensat`: set the timestamp to the current time.
+    pub const UTIME_NOW: i64 = (1 << 30) - 1;
+
+    /// Special `nsec` value for `utimensat`: leave the timestamp unchanged.
+    pub const UTIME_OMIT: i64 = (1 << 30) - 2;
+
+    /// Zero time.
+    pub const ZERO: Self = Self::new(0, 0);
+
+    /// Create a new `TimeSpec`.
+    pub const fn new(sec: i64, nsec: i64) -> Self {
+        Self { sec, nsec }
+    }
+
+    /// Create a `TimeSpec` from a total number of nanoseconds.
+    pub const fn from_nanos(nanos: i128) -> Self {
+        Self {
+            sec: nanos.div_euclid(NSEC_PER_SEC as i128) as i64,
+            nsec: nanos.rem_euclid(NSEC_PER_SEC as i128) as i64,
+        }
+    }
+
+    /// Total number of nanoseconds.
+    pub const fn as_nanos(&self) -> i128 {
+        self.sec as i128 * NSEC_PER_SEC as i128 + self.nsec as i128
+    }
+
+    /// Check if `nsec` is in range, as required by most syscalls.
+    pub const fn is_valid(&self) -> bool {
+        self.nsec >= 0 && self.nsec < NSEC_PER_SEC
+    }
+}
+
+impl From<Duration> for TimeSpec {
+    fn from(d: Duration) -> Self {
+        Self::new(d.as_secs() as i64, d.subsec_nanos() as i64)
+    }
+}
+
+impl TryFrom<TimeSpec> for Duration {
+    type Error = LinuxError;
+
+    /// Fail with `EINVAL` if the time is negative or `nsec` is out of range.
+    fn try_from(ts: TimeSpec) -> Result<Self, Self::Error> {
+        if ts.sec < 0 || !ts.is_valid() {
+            return Err(LinuxError::EINVAL);
+        }
+        Ok(Duration::new(ts.sec as u64, ts.nsec as u32))
+    }
+}
+
+/// Time in seconds and microseconds, the `timeval` of libc.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[repr(C)]
+pub struct TimeVal {
+    /// Seconds.
+    pub sec: i64,
+    /// Microseconds, in range `[0, USEC_PER_SEC)`.
+    pub usec: i64,
+}
+
+impl TimeVal {
+    /// Zero time.
+    pub const ZERO: Self = Self::new(0, 0);
+
+    /// Create a new `TimeVal`.
+    pub const fn new(sec: i64, usec: i64) -> Self {
+        Self { sec, usec }
+    }
+
+    /// Create a `TimeVal` from a total number of nanoseconds, truncating
+    /// towards negative infinity.
+    pub const fn from_nanos(nanos: i128) -> Self {
+        let ts = TimeSpec::from_nanos(nanos);
+        Self::new(ts.sec, ts.nsec / 1000)
+    }
+
+    /// Total number of nanoseconds.
+    pub const fn as_nanos(&self) -> i128 {
+        self.sec as i128 * NSEC_PER_SEC as i128 + self.usec as i128 * 1000
+    }
+
+    /// Check if `usec` is in range.
+    pub const fn is_valid(&self) -> bool {
+        self.usec >= 0 && self.usec < USEC_PER_SEC
+    }
+}
+
+impl From<Duration> for TimeVal {
+    fn from(d: Duration) -> Self {
+        Self::new(d.as_secs() as i64, d.subsec_micros() as i64)
+    }
+}
+
+impl TryFrom<TimeVal> for Duration {
+    type Error = LinuxError;
+
+    /// Fail with `EINVAL` if the time is negative or `usec` is out of range.
+    fn try_from(tv: TimeVal) -> Result<Self, Self::Error> {
+        if tv.sec < 0 || !tv.is_valid() {
+            return Err(LinuxError::EINVAL);
+        }
+        Ok(Duration::new(tv.sec as u64, tv.usec as u32 * 1000))
+    }
+}
+
+impl From<TimeVal> for TimeSpec {
+    fn from(tv: TimeVal) -> Self {
+        Self::new(tv.sec, tv.usec * 1000)
+    }
+}
+
+/// Interval timer specification, the `itimerspec` of libc.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[repr(C)]
+pub struct Itimerspec {
+    /// Interval for periodic timer, zero for a one-shot timer.
+    pub interval: TimeSpec,
+    /// Initial expiration, zero to disarm the timer.
+    pub value: TimeSpec,
+}
+
+impl Itimerspec {
+    /// Create a new `Itimerspec`.
+    pub const fn new(interval: TimeSpec, value: TimeSpec) -> Self {
+        Self { interval, value }
+    }
+
+    /// Check if the timer is disarmed.
+    pub const fn is_disarmed(&self) -> bool {
+        self.value.sec == 0 && self.value.nsec == 0
+    }
+}
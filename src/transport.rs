@@ -0,0 +1,466 @@
+//! Each message on a byte-stream transport is framed as a little-endian
+//! `u32` length followed by the message. A command message is the command
+//! id (a little-endian `u32`, as written by `id_to_bytes`) followed by the
+//! encoded command, and a response message is the encoded response.
+
+use crate::{id_from_wire, ID_SIZE};
+
+/// Checker side of a transport: sends encoded commands.
+pub trait CommandSink {
+    /// Transport error.
+    type Error;
+
+    /// Send the encoded command `cmd` with id `id`.
+    fn send(&mut self, id: usize, cmd: &[u8]) -> Result<(), Self::Error>;
+}
+
+/// Checker side of a transport: receives encoded responses.
+pub trait CommandSource {
+    /// Transport error.
+    type Error;
+
+    /// Receive an encoded response into `buf`, return its length.
+    fn recv(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error>;
+}
+
+/// Async version of [`CommandSink`].
+#[allow(async_fn_in_trait)]
+pub trait AsyncCommandSink {
+    /// Transport error.
+    type Error;
+
+    /// Send the encoded command `cmd` with id `id`.
+    async fn send(&mut self, id: usize, cmd: &[u8]) -> Result<(), Self::Error>;
+}
+
+/// Async version of [`CommandSource`].
+#[allow(async_fn_in_trait)]
+pub trait AsyncCommandSource {
+    /// Transport error.
+    type Error;
+
+    /// Receive an encoded response into `buf`, return its length.
+    async fn recv(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error>;
+}
+
+/// Async byte reader. Implement it for the reader type of your async runtime
+/// to use [`StreamTransport`] as an [`AsyncCommandSource`].
+#[allow(async_fn_in_trait)]
+pub trait AsyncReadExact {
+    /// Read error.
+    type Error;
+
+    /// Read exactly `buf.len()` bytes.
+    async fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Self::Error>;
+}
+
+/// Async byte writer. Implement it for the writer type of your async runtime
+/// to use [`StreamTransport`] as an [`AsyncCommandSink`].
+#[allow(async_fn_in_trait)]
+pub trait AsyncWriteAll {
+    /// Write error.
+    type Error;
+
+    /// Write all bytes of `buf`.
+    async fn write_all(&mut self, buf: &[u8]) -> Result<(), Self::Error>;
+
+    /// Flush buffered data.
+    async fn flush(&mut self) -> Result<(), Self::Error>;
+}
+
+/// Error of an async stream transport.
+#[derive(Debug)]
+pub enum FrameError<E> {
+    /// The underlying stream failed.
+    Io(E),
+    /// The frame does not fit in the buffer or in a `u32` length.
+    TooLarge,
+}
+
+impl<E> From<E> for FrameError<E> {
+    fn from(e: E) -> Self {
+        FrameError::Io(e)
+    }
+}
+
+/// Transport over a byte stream, with a reader for responses and a writer
+/// for commands.
+pub struct StreamTransport<R, W> {
+    reader: R,
+    writer: W,
+}
+
+impl<R, W> StreamTransport<R, W> {
+    /// Create a transport from a reader and a writer.
+    pub fn new(reader: R, writer: W) -> Self {
+        Self { reader, writer }
+    }
+
+    /// Return the reader and the writer.
+    pub fn into_inner(self) -> (R, W) {
+        (self.reader, self.writer)
+    }
+}
+
+impl<R, W: AsyncWriteAll> AsyncCommandSink for StreamTransport<R, W> {
+    type Error = FrameError<W::Error>;
+
+    async fn send(&mut self, id: usize, cmd: &[u8]) -> Result<(), Self::Error> {
+        let id = crate::id_to_wire(id);
+        let len = u32::try_from(id.len() + cmd.len()).map_err(|_| FrameError::TooLarge)?;
+        self.writer.write_all(&len.to_le_bytes()).await?;
+        self.writer.write_all(&id).await?;
+        self.writer.write_all(cmd).await?;
+        Ok(self.writer.flush().await?)
+    }
+}
+
+impl<R: AsyncReadExact, W> AsyncCommandSource for StreamTransport<R, W> {
+    type Error = FrameError<R::Error>;
+
+    async fn recv(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let mut len = [0; 4];
+        self.reader.read_exact(&mut len).await?;
+        let len = u32::from_le_bytes(len) as usize;
+        if len > buf.len() {
+            // Skip the frame so that the next one is read from its start.
+            let mut scratch = [0; 1];
+            let chunk = if buf.is_empty() {
+                &mut scratch[..]
+            } else {
+                buf
+            };
+            let mut left = len;
+            while left > 0 {
+                let n = left.min(chunk.len());
+                self.reader.read_exact(&mut chunk[..n]).await?;
+                left -= n;
+            }
+            return Err(FrameError::TooLarge);
+        }
+        self.reader.read_exact(&mut buf[..len]).await?;
+        Ok(len)
+    }
+}
+
+/// Size of the length prefix of a frame.
+const LEN_SIZE: usize = core::mem::size_of::<u32>();
+
+/// Incremental decoder of command frames arriving in arbitrary chunks, e.g.
+/// from a serial port, buffering at most `N` bytes.
+///
+/// Frames whose message does not fit in the buffer are dropped.
+pub struct Decoder<const N: usize> {
+    buf: [u8; N],
+    /// Number of buffered bytes.
+    len: usize,
+    /// Bytes of the frame returned by the last `next_frame`, to be discarded.
+    pending: usize,
+    /// Bytes of an oversized frame still to be skipped.
+    skip: usize,
+    /// Number of dropped frames.
+    dropped: usize,
+}
+
+impl<const N: usize> Default for Decoder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Decoder<N> {
+    /// Create an empty decoder.
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+            pending: 0,
+            skip: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Discard the frame returned by the last `next_frame`.
+    fn discard_pending(&mut self) {
+        if self.pending > 0 {
+            self.buf.copy_within(self.pending..self.len, 0);
+            self.len -= self.pending;
+            self.pending = 0;
+        }
+    }
+
+    /// Buffer bytes from `data`, return how many were consumed. Call
+    /// `next_frame` until it returns `None` and feed the rest of `data` again.
+    pub fn feed(&mut self, data: &[u8]) -> usize {
+        self.discard_pending();
+        if self.skip > 0 {
+            let n = self.skip.min(data.len());
+            self.skip -= n;
+            return n;
+        }
+        let n = (N - self.len).min(data.len());
+        self.buf[self.len..self.len + n].copy_from_slice(&data[..n]);
+        self.len += n;
+        n
+    }
+
+    /// Return the next complete frame as the command id and the encoded
+    /// command, if one is buffered.
+    pub fn next_frame(&mut self) -> Option<(usize, &[u8])> {
+        loop {
+            self.discard_pending();
+            if self.skip > 0 || self.len < LEN_SIZE {
+                return None;
+            }
+            let msg_len = u32::from_le_bytes(self.buf[..LEN_SIZE].try_into().unwrap()) as usize;
+            // Compare before adding the prefix, which could overflow a 32-bit
+            // `usize` for lengths sent by the peer.
+            if msg_len > N.saturating_sub(LEN_SIZE) || msg_len < ID_SIZE {
+                // Drop the frame, including bytes not received yet.
+                let buffered = msg_len.min(self.len - LEN_SIZE);
+                self.pending = LEN_SIZE + buffered;
+                self.skip = msg_len - buffered;
+                self.dropped += 1;
+                continue;
+            }
+            let frame_len = LEN_SIZE + msg_len;
+            if self.len < frame_len {
+                return None;
+            }
+            self.pending = frame_len;
+            let (id, cmd) = self.buf[LEN_SIZE..frame_len].split_at(ID_SIZE);
+            return Some((id_from_wire(id.try_into().unwrap()), cmd));
+        }
+    }
+
+    /// Number of frames dropped because they did not fit in the buffer.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+}
+
+#[cfg(feature = "std")]
+pub use self::stream::*;
+
+#[cfg(feature = "std")]
+mod stream {
+    use super::{CommandSink, CommandSource, StreamTransport};
+    use std::{
+        fs::{File, OpenOptions},
+        io::{self, Read, Write},
+        net::{TcpStream, ToSocketAddrs},
+        path::Path,
+    };
+
+    /// Transport over a TCP connection.
+    pub type TcpTransport = StreamTransport<TcpStream, TcpStream>;
+
+    /// Transport over a serial port device, e.g. `/dev/ttyS0` or a pty
+    /// connected to QEMU. The port must be configured (baud rate, raw mode)
+    /// beforehand.
+    pub type SerialTransport = StreamTransport<File, File>;
+
+    /// File-backed transport: commands are recorded into one file and
+    /// responses are replayed from another.
+    pub type FileTransport = StreamTransport<File, File>;
+
+    impl TcpTransport {
+        /// Connect to a harness listening on `addr`.
+        pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+            let stream = TcpStream::connect(addr)?;
+            stream.set_nodelay(true)?;
+            Ok(Self::new(stream.try_clone()?, stream))
+        }
+    }
+
+    impl StreamTransport<File, File> {
+        /// Open a serial port device for reading and writing.
+        pub fn serial<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+            let port = OpenOptions::new().read(true).write(true).open(path)?;
+            Ok(Self::new(port.try_clone()?, port))
+        }
+
+        /// Record commands into `record` and replay responses from `replay`.
+        pub fn file<P: AsRef<Path>, Q: AsRef<Path>>(record: P, replay: Q) -> io::Result<Self> {
+            Ok(Self::new(File::open(replay)?, File::create(record)?))
+        }
+    }
+
+    impl<R, W: Write> CommandSink for StreamTransport<R, W> {
+        type Error = io::Error;
+
+        fn send(&mut self, id: usize, cmd: &[u8]) -> io::Result<()> {
+            let id = crate::id_to_wire(id);
+            let len = u32::try_from(id.len() + cmd.len())
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "command too large"))?;
+            self.writer.write_all(&len.to_le_bytes())?;
+            self.writer.write_all(&id)?;
+            self.writer.write_all(cmd)?;
+            self.writer.flush()
+        }
+    }
+
+    impl<R: Read, W> CommandSource for StreamTransport<R, W> {
+        type Error = io::Error;
+
+        fn recv(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let mut len = [0; 4];
+            self.reader.read_exact(&mut len)?;
+            let len = u32::from_le_bytes(len) as usize;
+            if len > buf.len() {
+                // Skip the frame so that the next one is read from its start.
+                let skipped = io::copy(&mut (&mut self.reader).take(len as u64), &mut io::sink())?;
+                if skipped < len as u64 {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "response larger than buffer",
+                ));
+            }
+            self.reader.read_exact(&mut buf[..len])?;
+            Ok(len)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    /// Frame `msg` with its length prefix.
+    fn frame(out: &mut [u8], msg: &[u8]) -> usize {
+        out[..LEN_SIZE].copy_from_slice(&(msg.len() as u32).to_le_bytes());
+        out[LEN_SIZE..LEN_SIZE + msg.len()].copy_from_slice(msg);
+        LEN_SIZE + msg.len()
+    }
+
+    /// Poll a future that never waits to completion.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    struct SliceReader<'a>(&'a [u8]);
+
+    impl AsyncReadExact for SliceReader<'_> {
+        type Error = ();
+
+        async fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), ()> {
+            if self.0.len() < buf.len() {
+                return Err(());
+            }
+            let (head, rest) = self.0.split_at(buf.len());
+            buf.copy_from_slice(head);
+            self.0 = rest;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn async_recv_skips_oversized_frame() {
+        let mut data = [0; 64];
+        let mut len = frame(&mut data, &[1; 20]);
+        len += frame(&mut data[len..], &[2, 3]);
+        let mut transport = StreamTransport::new(SliceReader(&data[..len]), ());
+        let mut buf = [0; 8];
+        let err = block_on(transport.recv(&mut buf));
+        assert!(matches!(err, Err(FrameError::TooLarge)));
+        assert_eq!(block_on(transport.recv(&mut buf)).unwrap(), 2);
+        assert_eq!(buf[..2], [2, 3]);
+
+        let mut transport = StreamTransport::new(SliceReader(&data[..len]), ());
+        assert!(matches!(
+            block_on(transport.recv(&mut [])),
+            Err(FrameError::TooLarge)
+        ));
+        assert!(matches!(block_on(transport.recv(&mut buf)), Ok(2)));
+    }
+
+    #[test]
+    fn decoder_frames() {
+        let mut data = [0; 64];
+        let mut len = frame(&mut data, &[7, 0, 0, 0, 0xaa, 0xbb]);
+        len += frame(&mut data[len..], &[8, 0, 0, 0]);
+        let mut decoder = Decoder::<32>::new();
+        // Feed one byte at a time, like a serial port.
+        let mut frames = 0;
+        for byte in &data[..len] {
+            assert_eq!(decoder.feed(core::slice::from_ref(byte)), 1);
+            while let Some((id, cmd)) = decoder.next_frame() {
+                match frames {
+                    0 => assert_eq!((id, cmd), (7, &[0xaa, 0xbb][..])),
+                    _ => assert_eq!((id, cmd), (8, &[][..])),
+                }
+                frames += 1;
+            }
+        }
+        assert_eq!(frames, 2);
+        assert_eq!(decoder.dropped(), 0);
+    }
+
+    #[test]
+    fn decoder_drops_huge_length() {
+        let mut decoder = Decoder::<16>::new();
+        assert_eq!(decoder.feed(&[0xff, 0xff, 0xff, 0xff, 1, 2]), 6);
+        assert!(decoder.next_frame().is_none());
+        assert_eq!(decoder.dropped(), 1);
+        // The rest of the frame is skipped as it arrives.
+        assert_eq!(decoder.feed(&[0; 8]), 8);
+        assert!(decoder.next_frame().is_none());
+        assert_eq!(decoder.skip, u32::MAX as usize - 10);
+    }
+
+    #[test]
+    fn decoder_drops_oversized_frames() {
+        let mut data = [0; 128];
+        let mut len = frame(&mut data, &[1; 40]);
+        len += frame(&mut data[len..], &[2]);
+        len += frame(&mut data[len..], &[3, 0, 0, 0, 9]);
+        let mut decoder = Decoder::<16>::new();
+        let mut input = &data[..len];
+        let mut seen = None;
+        while !input.is_empty() {
+            let n = decoder.feed(input);
+            input = &input[n..];
+            while let Some((id, cmd)) = decoder.next_frame() {
+                assert!(seen.is_none());
+                seen = Some((id, cmd[0]));
+            }
+        }
+        assert_eq!(seen, Some((3, 9)));
+        // The first frame exceeds the buffer, the second is shorter than an id.
+        assert_eq!(decoder.dropped(), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn recv_skips_oversized_frame() {
+        use std::io::Cursor;
+
+        let mut data = [0; 64];
+        let mut len = frame(&mut data, &[1; 20]);
+        len += frame(&mut data[len..], &[2, 3]);
+        let mut transport = StreamTransport::new(Cursor::new(&data[..len]), ());
+        let mut buf = [0; 8];
+        let err = CommandSource::recv(&mut transport, &mut buf).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(CommandSource::recv(&mut transport, &mut buf).unwrap(), 2);
+        assert_eq!(buf[..2], [2, 3]);
+
+        // A truncated oversized frame is reported as such.
+        let mut transport = StreamTransport::new(Cursor::new(&data[..10]), ());
+        let err = CommandSource::recv(&mut transport, &mut buf).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,57 @@
+use crate::error::LinuxError;
+use core::fmt;
+
+/// Reason why a command is inconsistent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValidationError {
+    /// A path argument is empty.
+    EmptyPath,
+    /// A file descriptor argument is negative (and not `AT_FDCWD` where
+    /// allowed).
+    BadFd,
+    /// A file is created without any permission bits.
+    MissingMode,
+    /// A length argument is zero.
+    ZeroLength,
+    /// An address argument is not page aligned.
+    Unaligned,
+    /// Flags that cannot be combined, or a required flag is missing.
+    ConflictingFlags,
+}
+
+impl ValidationError {
+    /// Error the kernel is expected to return for the command, or `None` if
+    /// the command is merely suspicious and should succeed.
+    pub fn expected_errno(self) -> Option<LinuxError> {
+        match self {
+            Self::EmptyPath => Some(LinuxError::ENOENT),
+            Self::BadFd => Some(LinuxError::EBADF),
+            Self::MissingMode => None,
+            Self::ZeroLength | Self::Unaligned | Self::ConflictingFlags => Some(LinuxError::EINVAL),
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::EmptyPath => "empty path",
+            Self::BadFd => "bad file descriptor",
+            Self::MissingMode => "file created without mode",
+            Self::ZeroLength => "zero length",
+            Self::Unaligned => "unaligned address",
+            Self::ConflictingFlags => "conflicting flags",
+        })
+    }
+}
+
+/// Semantic validation of command arguments, so that generators and parsers
+/// can reject nonsensical commands, or send them on purpose to test error
+/// paths.
+pub trait Validate {
+    /// Check the consistency of the arguments. Commands without constraints
+    /// are always valid.
+    fn validate(&self) -> Result<(), ValidationError> {
+        Ok(())
+    }
+}